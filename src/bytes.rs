@@ -0,0 +1,433 @@
+//! Regular expressions that match arbitrary byte strings.
+//!
+//! The API mirrors the one for `&str`, except that patterns and haystacks are
+//! given as `&[u8]`, so they may contain NUL bytes and invalid UTF-8, and
+//! matched text is returned as `&[u8]`. By default patterns are compiled with
+//! `ENCODING_ASCII`, where every byte is a single character.
+use std::{iter, ops};
+use super::{CaptureNames, Error, Encoding, Options, Region, RegexConfig, ENCODING_ASCII, ENCODING_UTF8,
+            OPTION_NONE};
+use super::regex::Successive;
+
+/// A compiled Oniguruma regular expression that matches byte strings.
+#[derive(Debug)]
+pub struct Regex {
+    inner: super::Regex,
+    utf8: bool
+}
+
+impl Regex {
+    /// Compiles a regular expression given as bytes with default options and
+    /// `ENCODING_ASCII`. Default syntax is `SYNTAX_RUBY`.
+    pub fn new(pattern: &[u8]) -> Result<Regex, Error> {
+        Regex::new_with_config(pattern, RegexConfig::default())
+    }
+
+    /// Compiles a regular expression given as bytes with `ENCODING_ASCII`.
+    pub fn new_with_config<'a>(pattern: &[u8], config: RegexConfig<'a>) -> Result<Regex, Error> {
+        Regex::new_with_encoding(pattern, config, ENCODING_ASCII)
+    }
+
+    /// Compiles a regular expression given as bytes in the specified
+    /// encoding. Both the pattern and the searched strings are interpreted
+    /// in that encoding.
+    pub fn new_with_encoding<'a>(pattern: &[u8],
+                                 config: RegexConfig<'a>,
+                                 encoding: &Encoding)
+                                 -> Result<Regex, Error> {
+        super::Regex::new_with_encoding(pattern, config, encoding).map(|inner| Regex {
+            inner: inner,
+            utf8: encoding as *const Encoding == ENCODING_UTF8 as *const Encoding
+        })
+    }
+
+    /// Search pattern in byte string and store search result into region
+    /// object.
+    ///
+    /// Returns match position offset if pattern is found, otherwise return
+    /// `None`. You also can use search time options: `OPTION_NOTBOL` and
+    /// `OPTION_NOTEOL`.
+    pub fn search_with_region(&self,
+                              text: &[u8],
+                              region: &mut Region,
+                              options: Options)
+                              -> Result<Option<usize>, Error> {
+        self.inner.search_raw(text, 0, text.len(), region, options)
+    }
+
+    /// Search pattern in byte string between offsets `start` and `range`
+    /// and store search result into region object. See `Regex::search_in`
+    /// for the `&str` version.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `start` or `range` is out of bounds.
+    pub fn search_in(&self,
+                     text: &[u8],
+                     start: usize,
+                     range: usize,
+                     region: &mut Region,
+                     options: Options)
+                     -> Result<Option<usize>, Error> {
+        self.inner.search_raw(text, start, range, region, options)
+    }
+
+    /// Match byte string and store search result into region object.
+    ///
+    /// Returns match length if pattern is found, otherwise return `None`.
+    pub fn match_with_region(&self,
+                             text: &[u8],
+                             region: &mut Region,
+                             options: Options)
+                             -> Result<Option<usize>, Error> {
+        self.inner.match_raw(text, 0, region, options)
+    }
+
+    /// Match byte string at offset `pos` and store search result into region
+    /// object. See `Regex::match_at` for the `&str` version.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pos` is out of bounds.
+    pub fn match_at(&self,
+                    text: &[u8],
+                    pos: usize,
+                    region: &mut Region,
+                    options: Options)
+                    -> Result<Option<usize>, Error> {
+        self.inner.match_raw(text, pos, region, options)
+    }
+
+    /// Returns the leftmost-first match in `text`. If no match exists, then
+    /// `None` is returned.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn find<'t>(&self, text: &'t [u8]) -> Option<Match<'t>> {
+        self.try_find(text).unwrap()
+    }
+
+    /// Returns the leftmost-first match in `text`. If no match exists, then
+    /// `Ok(None)` is returned.
+    ///
+    /// Unlike `find`, errors of Oniguruma engine are returned instead of
+    /// causing a panic.
+    pub fn try_find<'t>(&self, text: &'t [u8]) -> Result<Option<Match<'t>>, Error> {
+        let mut region = Region::new();
+        self.search_with_region(text, &mut region, OPTION_NONE)
+            .map(|r| r.and_then(|_| region.pos(0)))
+            .map(|pos| pos.map(|(beg, end)| Match { text: text, start: beg, end: end }))
+    }
+
+    /// Returns an iterator for each successive non-overlapping match in
+    /// `text`.
+    ///
+    /// # Panics
+    ///
+    /// The iterator may panic in the case of memory overflow during execution
+    /// or other internal errors of Oniguruma engine.
+    pub fn find_iter<'r, 't>(&'r self, text: &'t [u8]) -> FindMatches<'r, 't> {
+        FindMatches { caps: self.captures_iter(text) }
+    }
+
+    /// Returns an iterator for each successive non-overlapping match in
+    /// `text`, like `find_iter`, but yields errors of Oniguruma engine instead
+    /// of panicking. The iterator stops after the first error.
+    pub fn try_find_iter<'r, 't>(&'r self, text: &'t [u8]) -> TryFindMatches<'r, 't> {
+        TryFindMatches { inner: self.find_iter(text) }
+    }
+
+    /// Returns the capture groups corresponding to the leftmost-first match
+    /// in text. Capture group `0` always corresponds to the entire match.
+    /// If no match is found, then `None` is returned.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn captures<'t>(&self, text: &'t [u8]) -> Option<Captures<'t>> {
+        self.try_captures(text).unwrap()
+    }
+
+    /// Returns the capture groups corresponding to the leftmost-first match
+    /// in text. If no match is found, then `Ok(None)` is returned.
+    ///
+    /// Unlike `captures`, errors of Oniguruma engine are returned instead of
+    /// causing a panic.
+    pub fn try_captures<'t>(&self, text: &'t [u8]) -> Result<Option<Captures<'t>>, Error> {
+        let mut region = Region::new();
+        self.search_with_region(text, &mut region, OPTION_NONE)
+            .map(|r| r.map(|_| Captures { text: text, region: region }))
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups matched
+    /// in `text`.
+    ///
+    /// # Panics
+    ///
+    /// The iterator may panic in the case of memory overflow during execution
+    /// or other internal errors of Oniguruma engine.
+    pub fn captures_iter<'r, 't>(&'r self, text: &'t [u8]) -> FindCaptures<'r, 't> {
+        FindCaptures { inner: Successive::new(&self.inner, text, self.utf8) }
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups matched
+    /// in `text`, like `captures_iter`, but yields errors of Oniguruma engine
+    /// instead of panicking. The iterator stops after the first error.
+    pub fn try_captures_iter<'r, 't>(&'r self, text: &'t [u8]) -> TryFindCaptures<'r, 't> {
+        TryFindCaptures { inner: self.captures_iter(text) }
+    }
+
+    /// Returns an iterator over the names of the capture groups together with
+    /// the numbers of the groups carrying each name.
+    pub fn capture_names<'r>(&'r self) -> CaptureNames<'r> {
+        self.inner.capture_names()
+    }
+
+    pub fn captures_len(&self) -> usize {
+        self.inner.captures_len()
+    }
+
+    pub fn capture_histories_len(&self) -> usize {
+        self.inner.capture_histories_len()
+    }
+
+    pub fn names_len(&self) -> usize {
+        self.inner.names_len()
+    }
+
+}
+
+/// A single match of a regular expression or of one of its capture groups in
+/// a byte string.
+///
+/// `'t` is the lifetime of the matched text.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Match<'t> {
+    text: &'t [u8],
+    start: usize,
+    end: usize
+}
+
+impl<'t> Match<'t> {
+    /// Returns the starting byte offset of the match in the haystack.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the ending byte offset of the match in the haystack.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the range of byte offsets of the match in the haystack.
+    pub fn range(&self) -> ops::Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the matched bytes.
+    pub fn as_bytes(&self) -> &'t [u8] {
+        &self.text[self.start..self.end]
+    }
+
+    /// Returns the length of the match in bytes.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns true if and only if the match is empty.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Captures represents a group of captured byte strings for a single match.
+///
+/// The 0th capture always corresponds to the entire match. Positions
+/// returned from a capture group are always byte indices.
+///
+/// `'t` is the lifetime of the matched text.
+#[derive(Debug)]
+pub struct Captures<'t> {
+    text: &'t [u8],
+    region: Region
+}
+
+impl<'t> Captures<'t> {
+    /// Returns the start and end positions of the Nth capture group. Returns
+    /// `None` if i is not a valid capture group or if the capture group did
+    /// not match anything.
+    pub fn pos(&self, pos: usize) -> Option<(usize, usize)> {
+        self.region.pos(pos)
+    }
+
+    /// Returns the match for the capture group `i`. Returns `None` if `i`
+    /// isn't a valid capture group or didn't match anything.
+    pub fn get(&self, pos: usize) -> Option<Match<'t>> {
+        self.pos(pos).map(|(beg, end)| Match { text: self.text, start: beg, end: end })
+    }
+
+    /// Returns the matched bytes for the capture group `i`. If `i` isn't
+    /// a valid capture group or didn't match anything, then `None` is returned.
+    pub fn at(&self, pos: usize) -> Option<&'t [u8]> {
+        self.pos(pos).map(|(beg, end)| &self.text[beg..end])
+    }
+
+    /// Returns the number of captured groups.
+    pub fn len(&self) -> usize {
+        self.region.len()
+    }
+
+    /// Returns true if and only if there are no captured groups.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Creates an iterator of all the capture groups in order of appearance in
+    /// the regular expression.
+    pub fn iter(&'t self) -> SubCaptures<'t> {
+        SubCaptures { idx: 0, caps: self }
+    }
+
+    /// Creates an iterator of all the capture group positions in order of
+    /// appearance in the regular expression.
+    pub fn iter_pos(&'t self) -> SubCapturesPos<'t> {
+        SubCapturesPos { idx: 0, caps: self }
+    }
+}
+
+/// An iterator over capture groups for a particular match of a regular
+/// expression.
+///
+///`'t` is the lifetime of the matched text.
+pub struct SubCaptures<'t> {
+    idx: usize,
+    caps: &'t Captures<'t>
+}
+
+impl<'t> iter::Iterator for SubCaptures<'t> {
+    type Item = Option<&'t [u8]>;
+
+    fn next(&mut self) -> Option<Option<&'t [u8]>> {
+        if self.idx < self.caps.len() {
+            self.idx += 1;
+            Some(self.caps.at(self.idx - 1))
+        } else {
+            None
+        }
+    }
+}
+
+/// An iterator over capture group positions for a particular match of
+/// a regular expression.
+///
+/// `'t` is the lifetime of the matched text.
+pub struct SubCapturesPos<'t> {
+    idx: usize,
+    caps: &'t Captures<'t>
+}
+
+impl<'t> iter::Iterator for SubCapturesPos<'t> {
+    type Item = Option<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Option<(usize, usize)>> {
+        if self.idx < self.caps.len() {
+            self.idx += 1;
+            Some(self.caps.pos(self.idx - 1))
+        } else {
+            None
+        }
+    }
+}
+
+/// An iterator that yields all non-overlapping capture groups matching a
+/// particular regular expression in a byte string.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct FindCaptures<'r, 't> {
+    inner: Successive<'r, 't, [u8]>
+}
+
+impl<'r, 't> FindCaptures<'r, 't> {
+    fn try_next(&mut self) -> Result<Option<Captures<'t>>, Error> {
+        let mut region = Region::new();
+        let found = self.inner.next_match(&mut region)?;
+        Ok(found.map(|_| Captures { text: self.inner.text(), region: region }))
+    }
+}
+
+impl<'r, 't> iter::Iterator for FindCaptures<'r, 't> {
+    type Item = Captures<'t>;
+
+    fn next(&mut self) -> Option<Captures<'t>> {
+        self.try_next().unwrap()
+    }
+}
+
+/// An iterator that yields all non-overlapping capture groups matching a
+/// particular regular expression in a byte string, yielding an error instead
+/// of panicking if the search fails.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct TryFindCaptures<'r, 't> {
+    inner: FindCaptures<'r, 't>
+}
+
+impl<'r, 't> iter::Iterator for TryFindCaptures<'r, 't> {
+    type Item = Result<Captures<'t>, Error>;
+
+    fn next(&mut self) -> Option<Result<Captures<'t>, Error>> {
+        match self.inner.try_next() {
+            Ok(Some(caps)) => Some(Ok(caps)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err))
+        }
+    }
+}
+
+/// An iterator over all non-overlapping matches in a byte string.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct FindMatches<'r, 't> {
+    caps: FindCaptures<'r, 't>
+}
+
+impl<'r, 't> FindMatches<'r, 't> {
+    fn try_next(&mut self) -> Result<Option<Match<'t>>, Error> {
+        self.caps.try_next().map(|caps| caps.and_then(|caps| caps.get(0)))
+    }
+}
+
+impl<'r, 't> iter::Iterator for FindMatches<'r, 't> {
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Match<'t>> {
+        self.try_next().unwrap()
+    }
+}
+
+/// An iterator over all non-overlapping matches in a byte string, yielding
+/// an error instead of panicking if the search fails.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct TryFindMatches<'r, 't> {
+    inner: FindMatches<'r, 't>
+}
+
+impl<'r, 't> iter::Iterator for TryFindMatches<'r, 't> {
+    type Item = Result<Match<'t>, Error>;
+
+    fn next(&mut self) -> Option<Result<Match<'t>, Error>> {
+        match self.inner.try_next() {
+            Ok(Some(pos)) => Some(Ok(pos)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err))
+        }
+    }
+}
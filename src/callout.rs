@@ -0,0 +1,555 @@
+use libc::{c_int, c_long, c_uint, c_ulong, c_void};
+use std::any::Any;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, Once};
+use std::{ptr, slice};
+
+use super::{Encoding, Error, Regex};
+use super::regex::OnigRegex;
+
+pub(crate) type OnigCalloutArgs = *const c_void;
+
+pub(crate) type OnigCalloutFunc =
+    Option<extern "C" fn(args: OnigCalloutArgs, user_data: *mut c_void) -> c_int>;
+
+const ONIG_CALLOUT_TYPE_SINGLE: c_int = 0;
+
+pub(crate) const ONIG_TYPE_VOID: c_uint = 0;
+const ONIG_TYPE_LONG: c_uint = 1 << 0;
+const ONIG_TYPE_CHAR: c_uint = 1 << 1;
+const ONIG_TYPE_STRING: c_uint = 1 << 2;
+const ONIG_TYPE_TAG: c_uint = 1 << 4;
+
+/// Return code of a callout that lets the match continue.
+pub const CALLOUT_SUCCESS: i32 = 0;
+/// Return code of a callout that makes the current match path fail.
+pub const CALLOUT_FAIL: i32 = 1;
+
+// Returned to the engine to abort the search when a callout panics. The code
+// never reaches the caller: the panic is resumed as soon as the search returns.
+// It lies outside of the range of Oniguruma's own error codes.
+pub(crate) const CALLOUT_PANIC_ERROR: c_int = c_int::MIN;
+
+// Codes from -1 to `ONIG_ABORT` are not errors to the engine; together with
+// `CALLOUT_PANIC_ERROR` they are replaced by `ONIGERR_INVALID_ARGUMENT` when
+// returned through `CalloutResult::Error`.
+const ONIG_ABORT: c_int = -3;
+const ONIGERR_INVALID_ARGUMENT: c_int = -30;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct OnigStringValue {
+    start: *const u8,
+    end: *const u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) union OnigValue {
+    l: c_long,
+    c: u32,
+    s: OnigStringValue,
+    p: *mut c_void,
+    tag: c_int,
+}
+
+#[link(name="onig")]
+extern {
+    fn onig_set_callout_of_name(
+        enc: *const Encoding,
+        callout_type: c_int,
+        name: *const u8,
+        name_end: *const u8,
+        callout_in: c_int,
+        callout: OnigCalloutFunc,
+        end_callout: OnigCalloutFunc,
+        arg_num: c_int,
+        arg_types: *const c_uint,
+        optional_arg_num: c_int,
+        opt_defaults: *const OnigValue
+    ) -> c_int;
+
+    fn onig_set_progress_callout(f: OnigCalloutFunc) -> c_int;
+    fn onig_set_retraction_callout(f: OnigCalloutFunc) -> c_int;
+
+    fn onig_get_regex_by_callout_args(args: OnigCalloutArgs) -> OnigRegex;
+    fn onig_get_callout_num_by_callout_args(args: OnigCalloutArgs) -> c_int;
+    fn onig_get_callout_in_by_callout_args(args: OnigCalloutArgs) -> c_int;
+    fn onig_get_name_id_by_callout_args(args: OnigCalloutArgs) -> c_int;
+    fn onig_get_contents_by_callout_args(args: OnigCalloutArgs) -> *const u8;
+    fn onig_get_contents_end_by_callout_args(args: OnigCalloutArgs) -> *const u8;
+    fn onig_get_args_num_by_callout_args(args: OnigCalloutArgs) -> c_int;
+    fn onig_get_passed_args_num_by_callout_args(args: OnigCalloutArgs) -> c_int;
+    fn onig_get_arg_by_callout_args(
+        args: OnigCalloutArgs,
+        index: c_int,
+        arg_type: *mut c_uint,
+        val: *mut OnigValue
+    ) -> c_int;
+    fn onig_get_string_by_callout_args(args: OnigCalloutArgs) -> *const u8;
+    fn onig_get_string_end_by_callout_args(args: OnigCalloutArgs) -> *const u8;
+    fn onig_get_start_by_callout_args(args: OnigCalloutArgs) -> *const u8;
+    fn onig_get_right_range_by_callout_args(args: OnigCalloutArgs) -> *const u8;
+    fn onig_get_current_by_callout_args(args: OnigCalloutArgs) -> *const u8;
+    fn onig_get_retry_counter_by_callout_args(args: OnigCalloutArgs) -> c_ulong;
+    fn onig_get_capture_range_in_callout(
+        args: OnigCalloutArgs,
+        mem_num: c_int,
+        begin: *mut c_int,
+        end: *mut c_int
+    ) -> c_int;
+}
+
+/// When a callout is invoked during a match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalloutIn {
+    /// While the match progresses forward.
+    Progress = 1,
+    /// While the matcher backtracks over the callout.
+    Retraction = 2,
+    /// Both on progress and on retraction.
+    Both = 3,
+}
+
+/// Type of an argument accepted by a named callout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalloutArgType {
+    /// An integer, e.g. `(*name{10})`.
+    Long,
+    /// A single character.
+    Char,
+    /// An arbitrary string.
+    String,
+    /// A tag name, e.g. `(*name[tag])`.
+    Tag,
+}
+
+impl CalloutArgType {
+    fn to_raw(self) -> c_uint {
+        match self {
+            CalloutArgType::Long => ONIG_TYPE_LONG,
+            CalloutArgType::Char => ONIG_TYPE_CHAR,
+            CalloutArgType::String => ONIG_TYPE_STRING,
+            CalloutArgType::Tag => ONIG_TYPE_TAG,
+        }
+    }
+}
+
+/// A value of a callout argument or of a callout data slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalloutArg<'a> {
+    Long(i64),
+    Char(u32),
+    String(&'a [u8]),
+    Tag(i32),
+}
+
+impl<'a> CalloutArg<'a> {
+    pub(crate) unsafe fn from_raw(value_type: c_uint, val: &OnigValue) -> Option<CalloutArg<'a>> {
+        match value_type {
+            ONIG_TYPE_LONG => Some(CalloutArg::Long(val.l as i64)),
+            ONIG_TYPE_CHAR => Some(CalloutArg::Char(val.c)),
+            ONIG_TYPE_STRING => {
+                let len = val.s.end as usize - val.s.start as usize;
+                Some(CalloutArg::String(slice::from_raw_parts(val.s.start, len)))
+            }
+            ONIG_TYPE_TAG => Some(CalloutArg::Tag(val.tag as i32)),
+            _ => None
+        }
+    }
+}
+
+/// What the matcher should do after a callout returns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalloutResult {
+    /// Continue the match.
+    Success,
+    /// Fail the current match path and backtrack.
+    Fail,
+    /// Abort the search with the given error code, which must be less
+    /// than `-3`. The search returns it as an `Error`. Codes that are not
+    /// error codes, or that are reserved by the engine, abort the search
+    /// with the invalid argument error instead.
+    Error(i32),
+}
+
+impl CalloutResult {
+    pub(crate) fn to_raw(self) -> c_int {
+        match self {
+            CalloutResult::Success => CALLOUT_SUCCESS,
+            CalloutResult::Fail => CALLOUT_FAIL,
+            CalloutResult::Error(code) => {
+                let code = code as c_int;
+                if code >= ONIG_ABORT || code == CALLOUT_PANIC_ERROR {
+                    ONIGERR_INVALID_ARGUMENT
+                } else {
+                    code
+                }
+            }
+        }
+    }
+}
+
+/// Information passed to a callout about the match in progress.
+///
+/// Positions are byte offsets into the subject string.
+pub struct CalloutArgs<'a> {
+    raw: OnigCalloutArgs,
+    marker: PhantomData<&'a ()>,
+}
+
+impl<'a> CalloutArgs<'a> {
+    pub(crate) fn new(raw: OnigCalloutArgs) -> CalloutArgs<'a> {
+        CalloutArgs { raw: raw, marker: PhantomData }
+    }
+
+    /// Callout number, unique within the regex, starting from 1.
+    pub fn callout_num(&self) -> i32 {
+        unsafe { onig_get_callout_num_by_callout_args(self.raw) as i32 }
+    }
+
+    /// Returns `CalloutIn::Progress` or `CalloutIn::Retraction`.
+    pub fn callout_in(&self) -> CalloutIn {
+        let r = unsafe { onig_get_callout_in_by_callout_args(self.raw) };
+        if r == CalloutIn::Retraction as c_int {
+            CalloutIn::Retraction
+        } else {
+            CalloutIn::Progress
+        }
+    }
+
+    /// Name id of a named callout, as returned by `Callouts::register_named`.
+    pub fn name_id(&self) -> i32 {
+        unsafe { onig_get_name_id_by_callout_args(self.raw) as i32 }
+    }
+
+    /// Contents of a `(?{...})` callout, or `None` for named callouts.
+    pub fn contents(&self) -> Option<&'a [u8]> {
+        unsafe {
+            let start = onig_get_contents_by_callout_args(self.raw);
+            if start.is_null() {
+                return None
+            }
+            let end = onig_get_contents_end_by_callout_args(self.raw);
+            Some(slice::from_raw_parts(start, end as usize - start as usize))
+        }
+    }
+
+    /// Number of arguments the callout was registered with.
+    pub fn args_len(&self) -> usize {
+        unsafe { onig_get_args_num_by_callout_args(self.raw) as usize }
+    }
+
+    /// Number of arguments written in the pattern.
+    pub fn passed_args_len(&self) -> usize {
+        unsafe { onig_get_passed_args_num_by_callout_args(self.raw) as usize }
+    }
+
+    /// Returns the argument at `index`, or `None` if there is none.
+    pub fn arg(&self, index: usize) -> Option<CalloutArg<'a>> {
+        let mut arg_type: c_uint = ONIG_TYPE_VOID;
+        unsafe {
+            let mut val: OnigValue = ::std::mem::zeroed();
+            let r = onig_get_arg_by_callout_args(
+                self.raw, index as c_int, &mut arg_type, &mut val
+            );
+            if r != 0 {
+                return None
+            }
+            CalloutArg::from_raw(arg_type, &val)
+        }
+    }
+
+    /// The whole subject string.
+    pub fn subject(&self) -> &'a [u8] {
+        unsafe {
+            let start = onig_get_string_by_callout_args(self.raw);
+            let end = onig_get_string_end_by_callout_args(self.raw);
+            slice::from_raw_parts(start, end as usize - start as usize)
+        }
+    }
+
+    fn offset(&self, p: *const u8) -> usize {
+        let start = unsafe { onig_get_string_by_callout_args(self.raw) };
+        p as usize - start as usize
+    }
+
+    /// Position where the current match attempt started.
+    pub fn start(&self) -> usize {
+        self.offset(unsafe { onig_get_start_by_callout_args(self.raw) })
+    }
+
+    /// End of the search range.
+    pub fn right_range(&self) -> usize {
+        self.offset(unsafe { onig_get_right_range_by_callout_args(self.raw) })
+    }
+
+    /// Current matching position.
+    pub fn current(&self) -> usize {
+        self.offset(unsafe { onig_get_current_by_callout_args(self.raw) })
+    }
+
+    /// Number of retries made so far in the current match attempt.
+    pub fn retry_counter(&self) -> u64 {
+        unsafe { onig_get_retry_counter_by_callout_args(self.raw) as u64 }
+    }
+
+    /// Returns the position of capture group `group` as it is at this point
+    /// of the match, or `None` if the group hasn't matched.
+    pub fn capture_pos(&self, group: usize) -> Option<(usize, usize)> {
+        let (mut begin, mut end) = (0, 0);
+        let r = unsafe {
+            onig_get_capture_range_in_callout(self.raw, group as c_int, &mut begin, &mut end)
+        };
+        if r != 0 || begin < 0 || end < 0 {
+            None
+        } else {
+            Some((begin as usize, end as usize))
+        }
+    }
+}
+
+type NamedCallout = dyn Fn(&CalloutArgs) -> CalloutResult + Send + Sync;
+
+// Closures of named callouts, by name id.
+static NAMED_CALLOUTS: Mutex<Vec<(c_int, Arc<NamedCallout>)>> = Mutex::new(Vec::new());
+
+type RegexCallout = dyn Fn(&CalloutArgs) -> CalloutResult;
+
+thread_local! {
+    static CALLOUT_PANIC: RefCell<Option<Box<dyn Any + Send>>> = RefCell::new(None);
+
+    // Handlers of contents callouts, by compiled regex. `Regex` is not `Send`,
+    // so a regex is only ever searched on the thread that registered it.
+    static CONTENTS_CALLOUTS: RefCell<Vec<(usize, Rc<RegexCallout>)>> =
+        RefCell::new(Vec::new());
+
+    // Closures of named callouts set on a compiled regex, by regex and name
+    // id. They take precedence over the closures of `NAMED_CALLOUTS`.
+    static REGEX_NAMED_CALLOUTS: RefCell<Vec<(usize, c_int, Rc<RegexCallout>)>> =
+        RefCell::new(Vec::new());
+}
+
+static INSTALL_CONTENTS_CALLOUT: Once = Once::new();
+
+/// Run `f` for a callout, catching a panic so that it doesn't unwind through
+/// the engine. The panic is resumed by `resume_callout_panic`.
+pub(crate) fn call_guarded<F: FnOnce() -> CalloutResult>(f: F) -> c_int {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result.to_raw(),
+        Err(err) => {
+            CALLOUT_PANIC.with(|p| *p.borrow_mut() = Some(err));
+            CALLOUT_PANIC_ERROR
+        }
+    }
+}
+
+/// Resume a panic raised by a callout during the last search, if any.
+pub(crate) fn resume_callout_panic() {
+    if let Some(err) = CALLOUT_PANIC.with(|p| p.borrow_mut().take()) {
+        panic::resume_unwind(err)
+    }
+}
+
+extern "C" fn named_callout(args: OnigCalloutArgs, _user_data: *mut c_void) -> c_int {
+    let reg = unsafe { onig_get_regex_by_callout_args(args) } as usize;
+    let args = CalloutArgs::new(args);
+    let name_id = args.name_id() as c_int;
+    let regex_callout = REGEX_NAMED_CALLOUTS.with(|callouts| {
+        callouts.borrow()
+            .iter()
+            .find(|&&(raw, id, _)| raw == reg && id == name_id)
+            .map(|&(_, _, ref callout)| callout.clone())
+    });
+    if let Some(callout) = regex_callout {
+        return call_guarded(|| callout(&args))
+    }
+    let callout = NAMED_CALLOUTS.lock().unwrap()
+        .iter()
+        .find(|&&(id, _)| id == name_id)
+        .map(|&(_, ref callout)| callout.clone());
+    match callout {
+        Some(callout) => call_guarded(|| callout(&args)),
+        None => CALLOUT_SUCCESS
+    }
+}
+
+pub(crate) extern "C" fn contents_callout(args: OnigCalloutArgs,
+                                          _user_data: *mut c_void)
+                                          -> c_int {
+    let reg = unsafe { onig_get_regex_by_callout_args(args) } as usize;
+    let handler = CONTENTS_CALLOUTS.with(|callouts| {
+        callouts.borrow()
+            .iter()
+            .find(|&&(raw, _)| raw == reg)
+            .map(|&(_, ref handler)| handler.clone())
+    });
+    match handler {
+        Some(handler) => {
+            let args = CalloutArgs::new(args);
+            call_guarded(|| handler(&args))
+        }
+        None => CALLOUT_SUCCESS
+    }
+}
+
+/// Forget the callout handlers of a regex that is being freed.
+pub(crate) fn remove_regex_callouts(reg: OnigRegex) {
+    let _ = CONTENTS_CALLOUTS.try_with(|callouts| {
+        callouts.borrow_mut().retain(|&(raw, _)| raw != reg as usize)
+    });
+    let _ = REGEX_NAMED_CALLOUTS.try_with(|callouts| {
+        callouts.borrow_mut().retain(|&(raw, _, _)| raw != reg as usize)
+    });
+}
+
+impl Regex {
+    /// Set the handler invoked for the `(?{...})` contents callouts of this
+    /// regex, replacing the previous one. The handler is shared with clones
+    /// of the regex.
+    ///
+    /// The handler receives the contents of the callout through
+    /// `CalloutArgs::contents`, along with the current position and the state
+    /// of the capture groups. Contents callouts are only recognized by syntaxes
+    /// with `SYNTAX_OPERATOR_QMARK_BRACE_CALLOUT_CONTENTS`, such as
+    /// `SYNTAX_ONIGURUMA`.
+    ///
+    /// A panic in `handler` aborts the search and is resumed once it returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oniguruma::{CalloutResult, Regex, RegexConfig, OPTION_NONE, SYNTAX_ONIGURUMA};
+    ///
+    /// let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    /// let mut r = Regex::new_with_config(r"\d+(?{even})", config).unwrap();
+    /// r.set_contents_callout(|args| {
+    ///     let digits = &args.subject()[args.start()..args.current()];
+    ///     if args.contents() == Some(b"even") && digits[digits.len() - 1] % 2 != 0 {
+    ///         CalloutResult::Fail
+    ///     } else {
+    ///         CalloutResult::Success
+    ///     }
+    /// });
+    /// assert_eq!(r.find("13 24").unwrap().as_str(), "24");
+    /// ```
+    pub fn set_contents_callout<F>(&mut self, handler: F)
+        where F: Fn(&CalloutArgs) -> CalloutResult + 'static
+    {
+        INSTALL_CONTENTS_CALLOUT.call_once(|| unsafe {
+            onig_set_progress_callout(Some(contents_callout));
+            onig_set_retraction_callout(Some(contents_callout));
+        });
+        let reg = self.as_raw() as usize;
+        CONTENTS_CALLOUTS.with(|callouts| {
+            let mut callouts = callouts.borrow_mut();
+            callouts.retain(|&(raw, _)| raw != reg);
+            callouts.push((reg, Rc::new(handler)));
+        });
+    }
+
+    /// Set the closure invoked for the named callout `name_id` in this regex,
+    /// replacing the one given to `Callouts::register_named`. Other regexes
+    /// keep using their own closure for the same name. The closure is shared
+    /// with clones of the regex.
+    ///
+    /// `name_id` is the id returned by `Callouts::register_named`; the name
+    /// must have been registered before the regex was compiled.
+    ///
+    /// A panic in `callout` aborts the search and is resumed once it returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oniguruma::{Callouts, CalloutIn, CalloutResult, Regex, RegexConfig,
+    ///                 ENCODING_UTF8, OPTION_NONE, SYNTAX_ONIGURUMA};
+    ///
+    /// let id = Callouts::register_named(ENCODING_UTF8, "CHECK", CalloutIn::Progress, &[],
+    ///                                   |_| CalloutResult::Success).unwrap();
+    /// let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    /// let r1 = Regex::new_with_config("a(*CHECK)|b", config).unwrap();
+    /// let mut r2 = Regex::new_with_config("a(*CHECK)|b", config).unwrap();
+    /// r2.set_named_callout(id, |_| CalloutResult::Fail);
+    /// assert_eq!(r1.find("ab").unwrap().start(), 0);
+    /// assert_eq!(r2.find("ab").unwrap().start(), 1);
+    /// ```
+    pub fn set_named_callout<F>(&mut self, name_id: i32, callout: F)
+        where F: Fn(&CalloutArgs) -> CalloutResult + 'static
+    {
+        let reg = self.as_raw() as usize;
+        let name_id = name_id as c_int;
+        REGEX_NAMED_CALLOUTS.with(|callouts| {
+            let mut callouts = callouts.borrow_mut();
+            callouts.retain(|&(raw, id, _)| raw != reg || id != name_id);
+            callouts.push((reg, name_id, Rc::new(callout)));
+        });
+    }
+}
+
+/// Registration of callouts invoked by the engine during a match.
+#[derive(Clone, Copy, Debug)]
+pub struct Callouts;
+
+impl Callouts {
+    /// Register `callout` to be invoked for `(*name)` and `(*name{args})`
+    /// in patterns of the given encoding, and return its name id.
+    ///
+    /// Callouts are only recognized by syntaxes with
+    /// `SYNTAX_OPERATOR_ASTERISK_CALLOUT_NAME`, such as `SYNTAX_ONIGURUMA`.
+    /// Registration is process-wide and must happen before the patterns
+    /// using the callout are compiled. Registering an already registered
+    /// name replaces its closure. `arg_types` lists the types of the
+    /// arguments the callout takes, at most 4.
+    ///
+    /// A panic in `callout` aborts the search and is resumed once it
+    /// returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oniguruma::{Callouts, CalloutIn, CalloutResult, Regex, RegexConfig,
+    ///                 ENCODING_UTF8, OPTION_NONE, SYNTAX_ONIGURUMA};
+    ///
+    /// Callouts::register_named(ENCODING_UTF8, "NEVER", CalloutIn::Progress, &[],
+    ///                          |_| CalloutResult::Fail).unwrap();
+    /// let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    /// let r = Regex::new_with_config("a(*NEVER)|b", config).unwrap();
+    /// assert_eq!(r.find("ab").unwrap().start(), 1);
+    /// ```
+    pub fn register_named<F>(encoding: &Encoding,
+                             name: &str,
+                             callout_in: CalloutIn,
+                             arg_types: &[CalloutArgType],
+                             callout: F)
+                             -> Result<i32, Error>
+        where F: Fn(&CalloutArgs) -> CalloutResult + Send + Sync + 'static
+    {
+        let name_bytes = name.as_bytes();
+        let types: Vec<c_uint> = arg_types.iter().map(|t| t.to_raw()).collect();
+        let mut callouts = NAMED_CALLOUTS.lock().unwrap();
+        let r = unsafe {
+            onig_set_callout_of_name(
+                encoding,
+                ONIG_CALLOUT_TYPE_SINGLE,
+                name_bytes.as_ptr(),
+                name_bytes[name_bytes.len()..].as_ptr(),
+                callout_in as c_int,
+                Some(named_callout),
+                None,
+                types.len() as c_int,
+                if types.is_empty() { ptr::null() } else { types.as_ptr() },
+                0,
+                ptr::null()
+            )
+        };
+        if r < 0 {
+            return Err(Error::new(r, None))
+        }
+        callouts.retain(|&(id, _)| id != r);
+        callouts.push((r, Arc::new(callout)));
+        Ok(r as i32)
+    }
+}
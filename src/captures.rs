@@ -1,5 +1,6 @@
-use std::iter;
-use super::{Regex, Region, OPTION_NONE};
+use std::{iter, ops};
+use super::{Error, Regex, Region, OPTION_NONE};
+use super::regex::Successive;
 
 impl Regex {
     /// Returns the capture groups corresponding to the leftmost-first match
@@ -11,10 +12,87 @@ impl Regex {
     /// This method may panic in the case of memory overflow during execution or
     /// other internal errors of Oniguruma engine.
     pub fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        self.try_captures(text).unwrap()
+    }
+
+    /// Returns the capture groups corresponding to the leftmost-first match
+    /// in text. If no match is found, then `Ok(None)` is returned.
+    ///
+    /// Unlike `captures`, errors of Oniguruma engine are returned instead of
+    /// causing a panic.
+    pub fn try_captures<'t>(&self, text: &'t str) -> Result<Option<Captures<'t>>, Error> {
         let mut region = Region::new();
         self.search_with_region(text, &mut region, OPTION_NONE)
-            .unwrap()
-            .map(|_| Captures { text: text, region: region })
+            .map(|r| r.map(|_| Captures { text: text, region: region, regex: self.clone() }))
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups matched
+    /// in `text`. This is operationally the same as `find_iter` (except it
+    /// yields information about submatches).
+    ///
+    /// Positions of the captures are byte indices with respect to the whole
+    /// `text`, and the engine always sees the text preceding each match, so
+    /// lookbehind and `\G` behave as in a single search.
+    ///
+    /// # Panics
+    ///
+    /// The iterator may panic in the case of memory overflow during execution
+    /// or other internal errors of Oniguruma engine.
+    pub fn captures_iter<'r, 't>(&'r self, text: &'t str) -> FindCaptures<'r, 't> {
+        FindCaptures { inner: Successive::new(self, text, true) }
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups matched
+    /// in `text`, like `captures_iter`, but yields errors of Oniguruma engine
+    /// instead of panicking. The iterator stops after the first error.
+    pub fn try_captures_iter<'r, 't>(&'r self, text: &'t str) -> TryFindCaptures<'r, 't> {
+        TryFindCaptures { inner: self.captures_iter(text) }
+    }
+}
+
+/// A single match of a regular expression or of one of its capture groups.
+///
+/// `'t` is the lifetime of the matched text.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Match<'t> {
+    text: &'t str,
+    start: usize,
+    end: usize
+}
+
+impl<'t> Match<'t> {
+    pub(crate) fn new(text: &'t str, start: usize, end: usize) -> Match<'t> {
+        Match { text: text, start: start, end: end }
+    }
+
+    /// Returns the starting byte offset of the match in the haystack.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the ending byte offset of the match in the haystack.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the range of byte offsets of the match in the haystack.
+    pub fn range(&self) -> ops::Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the matched text.
+    pub fn as_str(&self) -> &'t str {
+        &self.text[self.start..self.end]
+    }
+
+    /// Returns the length of the match in bytes.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns true if and only if the match is empty.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
     }
 }
 
@@ -28,7 +106,8 @@ impl Regex {
 #[derive(Debug)]
 pub struct Captures<'t> {
     text: &'t str,
-    region: Region
+    region: Region,
+    regex: Regex
 }
 
 impl<'t> Captures<'t> {
@@ -40,12 +119,100 @@ impl<'t> Captures<'t> {
         self.region.pos(pos)
     }
 
+    /// Returns the match for the capture group `i`. Returns `None` if `i`
+    /// isn't a valid capture group or didn't match anything.
+    pub fn get(&self, pos: usize) -> Option<Match<'t>> {
+        self.pos(pos).map(|(beg, end)| Match::new(self.text, beg, end))
+    }
+
     /// Returns the matched string for the capture group `i`. If `i` isn't
     /// a valid capture group or didn't match anything, then `None` is returned.
     pub fn at(&self, pos: usize) -> Option<&'t str> {
         self.pos(pos).map(|(beg, end)| &self.text[beg..end])
     }
 
+    /// Returns the start and end positions of the capture group named `name`.
+    /// Returns `None` if there is no such group or it didn't match anything.
+    /// If several groups share the name, the last of them that matched is
+    /// used.
+    pub fn name_pos(&self, name: &str) -> Option<(usize, usize)> {
+        self.regex
+            .name_to_backref_number(name, Some(&self.region))
+            .and_then(|idx| self.pos(idx))
+    }
+
+    /// Returns the matched string for the capture group named `name`. If
+    /// there is no such group or it didn't match anything, then `None` is
+    /// returned.
+    pub fn name(&self, name: &str) -> Option<&'t str> {
+        self.name_pos(name).map(|(beg, end)| &self.text[beg..end])
+    }
+
+    /// Expands all instances of group references in `template` with the
+    /// corresponding capture groups and appends the result to `dst`.
+    ///
+    /// Groups may be referenced as `$n`, `${n}`, `${name}` or `\k<name>`.
+    /// Use `$$` to insert a literal `$`. References to groups that don't
+    /// exist or didn't match are replaced with the empty string; malformed
+    /// references are copied literally.
+    pub fn expand(&self, template: &str, dst: &mut String) {
+        let mut rest = template;
+        while let Some(i) = rest.find(|c| c == '$' || c == '\\') {
+            dst.push_str(&rest[..i]);
+            rest = &rest[i..];
+            let (group, len) = if rest.starts_with("$$") {
+                dst.push('$');
+                rest = &rest[2..];
+                continue
+            } else if rest.starts_with("${") {
+                match rest.find('}') {
+                    Some(close) => (Some(&rest[2..close]), close + 1),
+                    None => (None, 1)
+                }
+            } else if rest.starts_with("\\k<") {
+                match rest.find('>') {
+                    Some(close) => (Some(&rest[3..close]), close + 1),
+                    None => (None, 1)
+                }
+            } else if rest.starts_with('$') {
+                let digits = rest[1..].bytes().take_while(|b| b.is_ascii_digit()).count();
+                if digits > 0 {
+                    (Some(&rest[1..1 + digits]), 1 + digits)
+                } else {
+                    (None, 1)
+                }
+            } else {
+                (None, 1)
+            };
+            match group {
+                Some(group) => {
+                    let idx = match group.parse::<usize>() {
+                        Ok(idx) => Some(idx),
+                        Err(..) => self.regex.name_to_backref_number(group, Some(&self.region))
+                    };
+                    if let Some(s) = idx.and_then(|idx| self.at(idx)) {
+                        dst.push_str(s);
+                    }
+                }
+                None => dst.push_str(&rest[..len])
+            }
+            rest = &rest[len..];
+        }
+        dst.push_str(rest);
+    }
+
+    pub(crate) fn text(&self) -> &'t str {
+        self.text
+    }
+
+    pub(crate) fn region(&self) -> &Region {
+        &self.region
+    }
+
+    pub(crate) fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
     /// Returns the number of captured groups.
     pub fn len(&self) -> usize {
         self.region.len()
@@ -68,6 +235,13 @@ impl<'t> Captures<'t> {
     pub fn iter_pos(&'t self) -> SubCapturesPos<'t> {
         SubCapturesPos { idx: 0, caps: self }
     }
+
+    /// Creates an iterator of all the named capture groups in order of
+    /// appearance in the regular expression, yielding the name together with
+    /// the matched string.
+    pub fn iter_named(&'t self) -> SubCapturesNamed<'t> {
+        SubCapturesNamed { idx: 0, caps: self }
+    }
 }
 
 /// An iterator over capture groups for a particular match of a regular
@@ -114,3 +288,79 @@ impl<'t> iter::Iterator for SubCapturesPos<'t> {
         }
     }
 }
+
+/// An iterator over named capture groups for a particular match of a regular
+/// expression.
+///
+/// Groups sharing a name are yielded once, with the string captured by the
+/// last of them that matched. `'t` is the lifetime of the matched text.
+pub struct SubCapturesNamed<'t> {
+    idx: usize,
+    caps: &'t Captures<'t>
+}
+
+impl<'t> iter::Iterator for SubCapturesNamed<'t> {
+    type Item = (&'t str, Option<&'t str>);
+
+    fn next(&mut self) -> Option<(&'t str, Option<&'t str>)> {
+        let names = self.caps.regex.names();
+        if self.idx < names.len() {
+            self.idx += 1;
+            let name = &names[self.idx - 1].0;
+            Some((name, self.caps.name(name)))
+        } else {
+            None
+        }
+    }
+}
+
+/// An iterator that yields all non-overlapping capture groups matching a
+/// particular regular expression.
+///
+/// The iterator stops when no more matches can be found. `'r` is the lifetime
+/// of the compiled expression and `'t` is the lifetime of the matched string.
+pub struct FindCaptures<'r, 't> {
+    inner: Successive<'r, 't, str>
+}
+
+impl<'r, 't> FindCaptures<'r, 't> {
+    pub(crate) fn try_next(&mut self) -> Result<Option<Captures<'t>>, Error> {
+        let mut region = Region::new();
+        let found = self.inner.next_match(&mut region)?;
+        Ok(found.map(|_| Captures {
+            text: self.inner.text(),
+            region: region,
+            regex: self.inner.regex().clone()
+        }))
+    }
+}
+
+impl<'r, 't> iter::Iterator for FindCaptures<'r, 't> {
+    type Item = Captures<'t>;
+
+    fn next(&mut self) -> Option<Captures<'t>> {
+        self.try_next().unwrap()
+    }
+}
+
+/// An iterator that yields all non-overlapping capture groups matching a
+/// particular regular expression, yielding an error instead of panicking if
+/// the search fails.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct TryFindCaptures<'r, 't> {
+    inner: FindCaptures<'r, 't>
+}
+
+impl<'r, 't> iter::Iterator for TryFindCaptures<'r, 't> {
+    type Item = Result<Captures<'t>, Error>;
+
+    fn next(&mut self) -> Option<Result<Captures<'t>, Error>> {
+        match self.inner.try_next() {
+            Ok(Some(caps)) => Some(Ok(caps)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err))
+        }
+    }
+}
@@ -2,8 +2,14 @@ use libc::c_void;
 
 #[link(name="onig")]
 extern {
+    static OnigEncodingASCII: Encoding;
+    static OnigEncodingISO_8859_1: Encoding;
     static OnigEncodingUTF8: Encoding;
 }
 
 pub type Encoding = c_void;
-pub static ENCODING_UTF8: &'static Encoding = &OnigEncodingUTF8;
+/// ASCII, bytes above `0x7f` are treated as non-word characters
+pub static ENCODING_ASCII: &'static Encoding = unsafe { &OnigEncodingASCII };
+/// ISO 8859-1 (Latin-1)
+pub static ENCODING_ISO_8859_1: &'static Encoding = unsafe { &OnigEncodingISO_8859_1 };
+pub static ENCODING_UTF8: &'static Encoding = unsafe { &OnigEncodingUTF8 };
@@ -31,8 +31,15 @@ bitflags! {
         const OPTION_NOTBOL = 512,
         /// String end isn't considered as end of line
         const OPTION_NOTEOL = 1024,
-        // const OPTION_POSIX_REGION = 2048,
-        // const OPTION_MAXBIT = 4096
+        /// Store search result into `PosixRegion`. This is search time option
+        /// and is only honoured by `search_with_posix_region` and
+        /// `match_with_posix_region`; other search methods ignore it.
+        const OPTION_POSIX_REGION = 2048,
+        /// Report every successful match path to the callback instead of
+        /// stopping at the first one. This is search time option and is
+        /// only honoured by `Regex::each_match`; other search methods
+        /// ignore it.
+        const OPTION_CALLBACK_EACH_MATCH = 1 << 25,
     }
 }
 
@@ -131,7 +138,11 @@ bitflags! {
         /// `\h, \H`
         const SYNTAX_OPERATOR_ESC_H_XDIGIT                = 1u64 << (32 + 19),
         /// `\`
-        const SYNTAX_OPERATOR_INEFFECTIVE_ESCAPE          = 1u64 << (32 + 20)
+        const SYNTAX_OPERATOR_INEFFECTIVE_ESCAPE          = 1u64 << (32 + 20),
+        /// `(?{...})`
+        const SYNTAX_OPERATOR_QMARK_BRACE_CALLOUT_CONTENTS = 1u64 << (32 + 24),
+        /// `(*name)`, `(*name{args})`
+        const SYNTAX_OPERATOR_ASTERISK_CALLOUT_NAME       = 1u64 << (32 + 25)
     }
 }
 
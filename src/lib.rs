@@ -21,20 +21,36 @@ extern crate libc;
 #[macro_use]
 extern crate bitflags;
 
+pub mod bytes;
+
 mod flags;
+mod callout;
 mod captures;
 mod encoding;
+mod limits;
+mod match_param;
 mod regex;
 mod region;
+mod regset;
+mod replace;
 mod syntax;
+mod tree;
+mod warning;
 
 #[cfg(test)]
 mod test;
 
 // re-export
 pub use flags::*;
+pub use callout::*;
 pub use captures::*;
 pub use encoding::*;
+pub use limits::*;
+pub use match_param::*;
 pub use regex::*;
 pub use region::*;
+pub use regset::*;
+pub use replace::*;
 pub use syntax::*;
+pub use tree::*;
+pub use warning::*;
@@ -0,0 +1,135 @@
+use libc::{c_int, c_uint, c_ulong};
+use std::convert::TryFrom;
+
+use super::Error;
+
+const ONIGERR_INVALID_ARGUMENT: c_int = -30;
+
+#[link(name="onig")]
+extern {
+    fn onig_get_match_stack_limit_size() -> c_uint;
+    fn onig_set_match_stack_limit_size(size: c_uint) -> c_int;
+    fn onig_get_retry_limit_in_match() -> c_ulong;
+    fn onig_set_retry_limit_in_match(n: c_ulong) -> c_int;
+    fn onig_get_retry_limit_in_search() -> c_ulong;
+    fn onig_set_retry_limit_in_search(n: c_ulong) -> c_int;
+    fn onig_get_subexp_call_limit_in_search() -> c_ulong;
+    fn onig_set_subexp_call_limit_in_search(n: c_ulong) -> c_int;
+    fn onig_get_parse_depth_limit() -> c_uint;
+    fn onig_set_parse_depth_limit(depth: c_uint) -> c_int;
+    fn onig_get_subexp_call_max_nest_level() -> c_int;
+    fn onig_set_subexp_call_max_nest_level(level: c_int) -> c_int;
+    fn onig_set_capture_num_limit(num: c_int) -> c_int;
+}
+
+/// Process-wide limits of the Oniguruma engine.
+///
+/// The limits are global: changing one affects every regular expression
+/// compiled or searched afterwards, in any thread. Per-search limits can be
+/// set with `MatchParam` instead.
+///
+/// # Examples
+///
+/// ```
+/// use oniguruma::EngineLimits;
+///
+/// EngineLimits::set_parse_depth_limit(512).unwrap();
+/// assert_eq!(EngineLimits::parse_depth_limit(), 512);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct EngineLimits;
+
+impl EngineLimits {
+    /// Maximum size of the backtracking stack, in number of entries.
+    /// `0` means unlimited.
+    pub fn match_stack_limit_size() -> u32 {
+        unsafe { onig_get_match_stack_limit_size() as u32 }
+    }
+
+    /// Set the maximum size of the backtracking stack.
+    pub fn set_match_stack_limit_size(size: u32) -> Result<(), Error> {
+        check(unsafe { onig_set_match_stack_limit_size(size as c_uint) })
+    }
+
+    /// Maximum number of backtracking retries for a match at a single
+    /// position. `0` means unlimited.
+    pub fn retry_limit_in_match() -> u64 {
+        unsafe { onig_get_retry_limit_in_match() as u64 }
+    }
+
+    /// Set the maximum number of backtracking retries for a match at
+    /// a single position.
+    pub fn set_retry_limit_in_match(limit: u64) -> Result<(), Error> {
+        let limit = c_ulong::try_from(limit).map_err(|_| invalid_argument())?;
+        check(unsafe { onig_set_retry_limit_in_match(limit) })
+    }
+
+    /// Maximum number of backtracking retries for a whole search.
+    /// `0` means unlimited.
+    pub fn retry_limit_in_search() -> u64 {
+        unsafe { onig_get_retry_limit_in_search() as u64 }
+    }
+
+    /// Set the maximum number of backtracking retries for a whole search.
+    pub fn set_retry_limit_in_search(limit: u64) -> Result<(), Error> {
+        let limit = c_ulong::try_from(limit).map_err(|_| invalid_argument())?;
+        check(unsafe { onig_set_retry_limit_in_search(limit) })
+    }
+
+    /// Maximum number of subexpression calls during a search.
+    /// `0` means unlimited.
+    pub fn subexp_call_limit_in_search() -> u64 {
+        unsafe { onig_get_subexp_call_limit_in_search() as u64 }
+    }
+
+    /// Set the maximum number of subexpression calls during a search.
+    pub fn set_subexp_call_limit_in_search(limit: u64) -> Result<(), Error> {
+        let limit = c_ulong::try_from(limit).map_err(|_| invalid_argument())?;
+        check(unsafe { onig_set_subexp_call_limit_in_search(limit) })
+    }
+
+    /// Maximum nesting depth of a pattern accepted by the parser.
+    pub fn parse_depth_limit() -> u32 {
+        unsafe { onig_get_parse_depth_limit() as u32 }
+    }
+
+    /// Set the maximum nesting depth of a pattern accepted by the parser.
+    /// `0` restores the default.
+    pub fn set_parse_depth_limit(depth: u32) -> Result<(), Error> {
+        check(unsafe { onig_set_parse_depth_limit(depth as c_uint) })
+    }
+
+    /// Maximum nesting level of subexpression calls.
+    pub fn subexp_call_max_nest_level() -> u32 {
+        unsafe { onig_get_subexp_call_max_nest_level() as u32 }
+    }
+
+    /// Set the maximum nesting level of subexpression calls.
+    ///
+    /// Levels above `i32::MAX` are rejected.
+    pub fn set_subexp_call_max_nest_level(level: u32) -> Result<(), Error> {
+        let level = c_int::try_from(level).map_err(|_| invalid_argument())?;
+        check(unsafe { onig_set_subexp_call_max_nest_level(level) })
+    }
+
+    /// Set the maximum number of capture groups in a pattern.
+    ///
+    /// Limits above `i32::MAX` are rejected. Oniguruma provides no way to
+    /// read this limit back.
+    pub fn set_capture_num_limit(limit: u32) -> Result<(), Error> {
+        let limit = c_int::try_from(limit).map_err(|_| invalid_argument())?;
+        check(unsafe { onig_set_capture_num_limit(limit) })
+    }
+}
+
+fn check(r: c_int) -> Result<(), Error> {
+    if r == 0 {
+        Ok(())
+    } else {
+        Err(Error::new(r, None))
+    }
+}
+
+fn invalid_argument() -> Error {
+    Error::new(ONIGERR_INVALID_ARGUMENT, None)
+}
@@ -0,0 +1,227 @@
+use libc::{c_int, c_uint, c_ulong, c_void};
+use std::cell::Cell;
+use std::mem;
+use super::{CalloutArg, Error, Regex};
+use super::callout::{contents_callout, OnigCalloutFunc, OnigValue, ONIG_TYPE_VOID};
+use super::regex::OnigRegex;
+
+pub(crate) type OnigMatchParam = *const c_void;
+
+// Number of data slots of each callout, `ONIG_CALLOUT_DATA_SLOT_NUM`.
+const CALLOUT_DATA_SLOT_NUM: usize = 5;
+const ONIGERR_MEMORY: c_int = -5;
+const ONIGERR_INVALID_ARGUMENT: c_int = -30;
+
+#[link(name="onig")]
+extern {
+    fn onig_new_match_param() -> OnigMatchParam;
+    fn onig_free_match_param(mp: OnigMatchParam);
+    fn onig_set_match_stack_limit_size_of_match_param(mp: OnigMatchParam, limit: c_uint) -> c_int;
+    fn onig_set_retry_limit_in_match_of_match_param(mp: OnigMatchParam, limit: c_ulong) -> c_int;
+    fn onig_set_retry_limit_in_search_of_match_param(mp: OnigMatchParam, limit: c_ulong) -> c_int;
+    // The `_dont_clear_old` variants, unlike `onig_get_callout_data` and
+    // `onig_get_callout_data_by_tag`, don't clear data left by earlier match
+    // attempts of the same search, so builtins such as `(*TOTAL_COUNT)` can be
+    // read after a match found at a later position.
+    fn onig_get_callout_data_dont_clear_old(
+        reg: OnigRegex,
+        mp: OnigMatchParam,
+        callout_num: c_int,
+        slot: c_int,
+        value_type: *mut c_uint,
+        val: *mut OnigValue
+    ) -> c_int;
+    fn onig_get_callout_data_by_tag_dont_clear_old(
+        reg: OnigRegex,
+        mp: OnigMatchParam,
+        tag: *const u8,
+        tag_end: *const u8,
+        slot: c_int,
+        value_type: *mut c_uint,
+        val: *mut OnigValue
+    ) -> c_int;
+    fn onig_set_callout_user_data_of_match_param(mp: OnigMatchParam, user_data: *mut c_void) -> c_int;
+    fn onig_set_progress_callout_of_match_param(mp: OnigMatchParam, f: OnigCalloutFunc) -> c_int;
+    fn onig_set_retraction_callout_of_match_param(mp: OnigMatchParam, f: OnigCalloutFunc) -> c_int;
+}
+
+/// Per-search parameters bounding the work done by a single search or match.
+///
+/// Pass it to `Regex::search_with_param` or `Regex::match_with_param`. When
+/// a limit is hit the search fails with an error for which
+/// `Error::is_limit_exceeded` returns true.
+#[derive(Debug)]
+pub struct MatchParam {
+    raw: OnigMatchParam,
+    // The regex last searched with these parameters and its number of
+    // callouts. Oniguruma only sizes the callout data for that regex.
+    searched: Cell<Option<(usize, c_int)>>
+}
+
+impl MatchParam {
+    /// Create match parameters initialized with the global engine defaults.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if Oniguruma fails to allocate memory.
+    pub fn new() -> MatchParam {
+        let raw = unsafe {
+            onig_new_match_param()
+        };
+        if raw.is_null() {
+            panic!("failed to allocate match param")
+        }
+        // dispatch contents callouts to the handlers set on each regex
+        unsafe {
+            onig_set_progress_callout_of_match_param(raw, Some(contents_callout));
+            onig_set_retraction_callout_of_match_param(raw, Some(contents_callout));
+        }
+        MatchParam { raw: raw, searched: Cell::new(None) }
+    }
+
+    /// Set the maximum size of the backtracking stack, in number of entries.
+    pub fn set_match_stack_limit_size(&mut self, limit: u32) {
+        unsafe {
+            onig_set_match_stack_limit_size_of_match_param(self.raw, limit as c_uint);
+        }
+    }
+
+    /// Set the maximum number of backtracking retries for a match at a single
+    /// position.
+    pub fn set_retry_limit_in_match(&mut self, limit: u64) {
+        unsafe {
+            onig_set_retry_limit_in_match_of_match_param(self.raw, limit as c_ulong);
+        }
+    }
+
+    /// Set the maximum number of backtracking retries for a whole search.
+    pub fn set_retry_limit_in_search(&mut self, limit: u64) {
+        unsafe {
+            onig_set_retry_limit_in_search_of_match_param(self.raw, limit as c_ulong);
+        }
+    }
+
+    /// Returns the value left in data slot `slot` of callout `callout_num`
+    /// of `regex` by the last search made with these parameters, or `None`
+    /// if the slot is not set.
+    ///
+    /// This is how results of builtin callouts such as `(*COUNT)`,
+    /// `(*TOTAL_COUNT)` or `(*MAX{n})` are read: their counter lives in
+    /// slot `0`. Data set during any match attempt of the search is kept.
+    ///
+    /// Returns an error if the last search made with these parameters was
+    /// not of `regex`, if `regex` has no callout `callout_num`, or if
+    /// `slot` is not less than 5.
+    pub fn callout_data(&self,
+                        regex: &Regex,
+                        callout_num: i32,
+                        slot: usize)
+                        -> Result<Option<CalloutArg<'_>>, Error> {
+        let count = self.searched_callout_count(regex, slot)?;
+        if callout_num < 1 || callout_num as c_int > count {
+            return Err(Error::new(ONIGERR_INVALID_ARGUMENT, None))
+        }
+        let mut value_type = ONIG_TYPE_VOID;
+        let r = unsafe {
+            let mut val: OnigValue = mem::zeroed();
+            let r = onig_get_callout_data_dont_clear_old(
+                regex.as_raw(),
+                self.raw,
+                callout_num as c_int,
+                slot as c_int,
+                &mut value_type,
+                &mut val
+            );
+            if r == 0 {
+                return Ok(CalloutArg::from_raw(value_type, &val))
+            }
+            r
+        };
+        if r > 0 {
+            Ok(None)
+        } else {
+            Err(Error::new(r, None))
+        }
+    }
+
+    /// Returns the value left in data slot `slot` of the callout tagged
+    /// `tag` in `regex`, e.g. `(*COUNT[tag])`, by the last search made with
+    /// these parameters, or `None` if the slot is not set.
+    ///
+    /// Fails like `callout_data`, and if `regex` has no such tag.
+    pub fn callout_data_by_tag(&self,
+                               regex: &Regex,
+                               tag: &str,
+                               slot: usize)
+                               -> Result<Option<CalloutArg<'_>>, Error> {
+        self.searched_callout_count(regex, slot)?;
+        let tag_bytes = tag.as_bytes();
+        let mut value_type = ONIG_TYPE_VOID;
+        let r = unsafe {
+            let mut val: OnigValue = mem::zeroed();
+            let r = onig_get_callout_data_by_tag_dont_clear_old(
+                regex.as_raw(),
+                self.raw,
+                tag_bytes.as_ptr(),
+                tag_bytes[tag_bytes.len()..].as_ptr(),
+                slot as c_int,
+                &mut value_type,
+                &mut val
+            );
+            if r == 0 {
+                return Ok(CalloutArg::from_raw(value_type, &val))
+            }
+            r
+        };
+        if r > 0 {
+            Ok(None)
+        } else {
+            Err(Error::new(r, None))
+        }
+    }
+
+    /// Returns the number of callouts of `regex` if it was the last regex
+    /// searched with these parameters and `slot` is a valid data slot.
+    fn searched_callout_count(&self, regex: &Regex, slot: usize) -> Result<c_int, Error> {
+        match self.searched.get() {
+            Some((raw, count)) if raw == regex.as_raw() as usize &&
+                                  slot < CALLOUT_DATA_SLOT_NUM => Ok(count),
+            _ => Err(Error::new(ONIGERR_INVALID_ARGUMENT, None))
+        }
+    }
+
+    /// Remember that `regex` was searched with these parameters, the search
+    /// returning `r`. Oniguruma only fails to size the callout data when
+    /// running out of memory.
+    pub(crate) fn record_search(&self, regex: &Regex, r: c_int) {
+        self.searched.set(if r == ONIGERR_MEMORY {
+            None
+        } else {
+            Some((regex.as_raw() as usize, regex.callout_count()))
+        });
+    }
+
+    pub(crate) fn set_callout_user_data(&mut self, user_data: *mut c_void) {
+        unsafe {
+            onig_set_callout_user_data_of_match_param(self.raw, user_data);
+        }
+    }
+
+    pub(crate) fn as_raw(&self) -> OnigMatchParam {
+        self.raw
+    }
+}
+
+impl Default for MatchParam {
+    fn default() -> MatchParam {
+        MatchParam::new()
+    }
+}
+
+impl Drop for MatchParam {
+    fn drop(&mut self) {
+        unsafe {
+            onig_free_match_param(self.raw);
+        }
+    }
+}
@@ -1,9 +1,18 @@
 use libc::{c_int, c_uint, c_void};
-use std::{error, fmt, str, ptr};
+use std::{error, fmt, iter, mem, str, ptr, slice};
+use std::any::Any;
+use std::ops::ControlFlow;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::Once;
 
-use super::{Region, Encoding, Options, Syntax, ENCODING_UTF8, SYNTAX_RUBY, OPTION_NONE};
+use super::{Captures, FindCaptures, Match, MatchParam, Region, PosixRegion, Encoding, Options, Syntax,
+            ENCODING_UTF8, SYNTAX_RUBY, OPTION_NONE, OPTION_POSIX_REGION,
+            OPTION_CALLBACK_EACH_MATCH};
+use super::callout::{remove_regex_callouts, resume_callout_panic};
+use super::match_param::OnigMatchParam;
 
-type OnigRegex = *const c_void;
+pub(crate) type OnigRegex = *const c_void;
 
 #[link(name="onig")]
 extern {
@@ -25,29 +34,129 @@ extern {
         end: *const u8,
         start: *const u8,
         range: *const u8,
-        region: *const Region,
+        region: *mut Region,
         option: c_uint
     ) -> c_int;
 
+    #[cfg(feature = "search-gpos")]
+    fn onig_search_gpos(
+        reg: OnigRegex,
+        str: *const u8,
+        end: *const u8,
+        global_pos: *const u8,
+        start: *const u8,
+        range: *const u8,
+        region: *mut Region,
+        option: c_uint
+    ) -> c_int;
+
+    fn onig_search_with_param(
+        reg: OnigRegex,
+        str: *const u8,
+        end: *const u8,
+        start: *const u8,
+        range: *const u8,
+        region: *mut Region,
+        option: c_uint,
+        mp: OnigMatchParam
+    ) -> c_int;
+
+    fn onig_match_with_param(
+        reg: OnigRegex,
+        str: *const u8,
+        end: *const u8,
+        at: *const u8,
+        region: *mut Region,
+        option: c_uint,
+        mp: OnigMatchParam
+    ) -> c_int;
+
     fn onig_match(
         reg: OnigRegex,
         str: *const u8,
         end: *const u8,
         at: *const u8,
-        region: *const Region,
+        region: *mut Region,
         option: c_uint
     ) -> c_int;
 
+    fn onig_name_to_backref_number(
+        reg: OnigRegex,
+        name: *const u8,
+        name_end: *const u8,
+        region: *const Region
+    ) -> c_int;
+
+    fn onig_name_to_group_numbers(
+        reg: OnigRegex,
+        name: *const u8,
+        name_end: *const u8,
+        nums: *mut *const c_int
+    ) -> c_int;
+
+    fn onig_foreach_name(
+        reg: OnigRegex,
+        func: extern "C" fn(*const u8, *const u8, c_int, *const c_int, OnigRegex, *mut c_void)
+                            -> c_int,
+        arg: *mut c_void
+    ) -> c_int;
+
+    fn onig_scan(
+        reg: OnigRegex,
+        str: *const u8,
+        end: *const u8,
+        region: *mut Region,
+        option: c_uint,
+        scan_callback: extern "C" fn(c_int, c_int, *const Region, *mut c_void) -> c_int,
+        callback_arg: *mut c_void
+    ) -> c_int;
+
     fn onig_number_of_names(reg: OnigRegex) -> c_int;
+    fn onig_set_callback_each_match(
+        f: extern "C" fn(*const u8, *const u8, *const u8, *const Region, *mut c_void) -> c_int
+    ) -> c_int;
+
+    fn onig_get_callout_num_by_tag(reg: OnigRegex, tag: *const u8, tag_end: *const u8) -> c_int;
+    // Not declared in `oniguruma.h`: exported by the library but private to
+    // it (`regparse.h`). It is the only way to bound callout numbers, see
+    // `Regex::callout_count`.
+    fn onig_reg_callout_list_at(reg: OnigRegex, num: c_int) -> *const c_void;
+
     fn onig_number_of_captures(reg: OnigRegex) -> c_int;
     fn onig_number_of_capture_histories(reg: OnigRegex) -> c_int;
 
     fn onig_free(reg: OnigRegex);
 }
 
+const ONIG_MISMATCH: c_int = -1;
+const ONIG_ABORT: c_int = -3;
+const ONIGERR_MATCH_STACK_LIMIT_OVER: c_int = -15;
+const ONIGERR_RETRY_LIMIT_IN_MATCH_OVER: c_int = -17;
+const ONIGERR_RETRY_LIMIT_IN_SEARCH_OVER: c_int = -18;
+const ONIGERR_SUBEXP_CALL_LIMIT_IN_SEARCH_OVER: c_int = -19;
+
+/// Converts the code returned by an Oniguruma search or match function: the
+/// position or length of the match, `ONIG_MISMATCH` or an error code.
+pub(crate) fn search_result(r: c_int) -> Result<Option<usize>, Error> {
+    if r >= 0 {
+        Ok(Some(r as usize))
+    } else if r == ONIG_MISMATCH {
+        Ok(None)
+    } else {
+        Err(Error::new(r, None))
+    }
+}
+
+/// Search time options passed to Oniguruma by the search methods: the kind
+/// of region is chosen by the method, and `OPTION_CALLBACK_EACH_MATCH` is
+/// reserved to `Regex::each_match`.
+pub(crate) fn search_options(options: Options) -> c_uint {
+    (options & !(OPTION_POSIX_REGION | OPTION_CALLBACK_EACH_MATCH)).bits()
+}
+
 #[repr(C)]
 #[derive(Debug)]
-struct OnigErrorInfo {
+pub(crate) struct OnigErrorInfo {
     enc: *const c_void, // TODO: change type to Encoding
     par: *const u8,
     par_end: *const u8
@@ -61,7 +170,7 @@ pub struct Error {
 }
 
 impl Error {
-    fn new(error: c_int, info: Option<OnigErrorInfo>) -> Error {
+    pub(crate) fn new(error: c_int, info: Option<OnigErrorInfo>) -> Error {
         let mut err_buff = &mut [0 as u8; 90];
         let len = unsafe {
             match info {
@@ -84,6 +193,18 @@ impl Error {
     pub fn code(&self) -> isize {
         self.error as isize
     }
+
+    /// Returns true if and only if the search was aborted because it hit
+    /// the match stack limit, a retry limit or the subexpression call limit.
+    pub fn is_limit_exceeded(&self) -> bool {
+        match self.error {
+            ONIGERR_MATCH_STACK_LIMIT_OVER |
+            ONIGERR_RETRY_LIMIT_IN_MATCH_OVER |
+            ONIGERR_RETRY_LIMIT_IN_SEARCH_OVER |
+            ONIGERR_SUBEXP_CALL_LIMIT_IN_SEARCH_OVER => true,
+            _ => false
+        }
+    }
 }
 
 impl fmt::Debug for Error {
@@ -108,6 +229,7 @@ impl error::Error for Error {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct RegexConfig<'a> {
     pub options: Options,
     pub syntax: &'a Syntax
@@ -126,9 +248,19 @@ impl Default for RegexConfig<'static> {
 }
 
 /// A compiled Oniguruma regular expression.
-#[derive(Debug)]
+///
+/// Cloning a `Regex` is cheap: all clones share the same compiled expression.
+#[derive(Clone, Debug)]
 pub struct Regex {
-    raw: OnigRegex
+    inner: Rc<RawRegex>
+}
+
+/// Compiled Oniguruma expression together with its group names, shared by
+/// a `Regex`, its clones and all captures produced by them.
+#[derive(Debug)]
+struct RawRegex {
+    raw: OnigRegex,
+    names: Vec<(String, Vec<usize>)>
 }
 
 impl Regex {
@@ -142,9 +274,17 @@ impl Regex {
     }
 
     pub fn new_with_config<'a>(pattern: &str, config: RegexConfig<'a>) -> Result<Regex, Error> {
+        Regex::new_with_encoding(pattern.as_bytes(), config, ENCODING_UTF8)
+    }
+
+    /// Compiles a regular expression given as raw bytes in the specified
+    /// encoding.
+    pub(crate) fn new_with_encoding<'a>(pattern_bytes: &[u8],
+                                        config: RegexConfig<'a>,
+                                        encoding: &Encoding)
+                                        -> Result<Regex, Error> {
         // Convert the rust types to those required for the call to
         // `onig_new`.
-        let pattern_bytes = pattern.as_bytes();
         let (start, end) = (
             pattern_bytes.as_ptr(),
             pattern_bytes[pattern_bytes.len()..].as_ptr()
@@ -166,13 +306,18 @@ impl Regex {
                 start,
                 end,
                 config.options.bits(),
-                ENCODING_UTF8,
+                encoding,
                 config.syntax,
                 &mut error)
         };
 
         if err == 0 {
-            Ok(Regex{ raw: reg })
+            let mut names = Vec::new();
+            unsafe {
+                onig_foreach_name(reg, push_name, &mut names as *mut _ as *mut c_void);
+            }
+            names.sort_by(|a: &(String, Vec<usize>), b| a.1.cmp(&b.1));
+            Ok(Regex { inner: Rc::new(RawRegex { raw: reg, names: names }) })
         } else {
             Err(Error::new(err, Some(error)))
         }
@@ -188,31 +333,114 @@ impl Regex {
                               region: &mut Region,
                               options: Options)
                               -> Result<Option<usize>, Error> {
-        let text_bytes = text.as_bytes();
-        let (start, end) = (
+        self.search_in(text, 0, text.len(), region, options)
+    }
+
+    /// Search pattern in string between byte offsets `start` and `range` and
+    /// store search result into region object.
+    ///
+    /// Only matches beginning in the window between `start` and `range` are
+    /// reported, but the engine still sees the whole `text`, so lookbehind,
+    /// `\b` and `^` take the text outside the window into account. If `range`
+    /// is less than `start` the search goes backward. The returned match
+    /// position is a byte offset with respect to `text`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `start` or `range` is out of bounds or doesn't
+    /// lie on a character boundary of `text`.
+    pub fn search_in(&self,
+                     text: &str,
+                     start: usize,
+                     range: usize,
+                     region: &mut Region,
+                     options: Options)
+                     -> Result<Option<usize>, Error> {
+        if !text.is_char_boundary(start) || !text.is_char_boundary(range) {
+            panic!("search window is not on a character boundary")
+        }
+        self.search_raw(text.as_bytes(), start, range, region, options)
+    }
+
+    /// Search pattern in a byte string between offsets `start` and `range`.
+    /// Offsets are only checked to be in bounds.
+    pub(crate) fn search_raw(&self,
+                             text_bytes: &[u8],
+                             start: usize,
+                             range: usize,
+                             region: &mut Region,
+                             options: Options)
+                             -> Result<Option<usize>, Error> {
+        let (beg, end) = (
             text_bytes.as_ptr(),
             text_bytes[text_bytes.len()..].as_ptr()
         );
 
         let r = unsafe {
             onig_search(
-                self.raw,
-                start,
-                end,
-                start,
+                self.inner.raw,
+                beg,
                 end,
+                text_bytes[start..].as_ptr(),
+                text_bytes[range..].as_ptr(),
                 region,
-                options.bits()
+                search_options(options)
             )
         };
+        resume_callout_panic();
 
-        if r >= 0 {
-            Ok(Some(r as usize))
-        } else if r == -1 {
-            Ok(None)
-        } else {
-            Err(Error::new(r, None))
+        search_result(r)
+    }
+
+    /// Search pattern in string between byte offsets `start` and `range`,
+    /// treating `gpos` as the position matched by the `\G` anchor, and store
+    /// search result into region object.
+    ///
+    /// This is the same as `search_in`, except that `\G` doesn't refer to
+    /// `start`, which is what scanners need to continue from the end of the
+    /// previous match.
+    ///
+    /// Only available with the `search-gpos` feature, which requires an
+    /// Oniguruma build exporting `onig_search_gpos` (6.9.8, for one, doesn't).
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `gpos`, `start` or `range` is out of bounds or
+    /// doesn't lie on a character boundary of `text`.
+    #[cfg(feature = "search-gpos")]
+    pub fn search_gpos(&self,
+                       text: &str,
+                       gpos: usize,
+                       start: usize,
+                       range: usize,
+                       region: &mut Region,
+                       options: Options)
+                       -> Result<Option<usize>, Error> {
+        if !text.is_char_boundary(gpos) || !text.is_char_boundary(start) ||
+           !text.is_char_boundary(range) {
+            panic!("search window is not on a character boundary")
         }
+        let text_bytes = text.as_bytes();
+        let (beg, end) = (
+            text_bytes.as_ptr(),
+            text_bytes[text_bytes.len()..].as_ptr()
+        );
+
+        let r = unsafe {
+            onig_search_gpos(
+                self.inner.raw,
+                beg,
+                end,
+                text_bytes[gpos..].as_ptr(),
+                text_bytes[start..].as_ptr(),
+                text_bytes[range..].as_ptr(),
+                region,
+                search_options(options)
+            )
+        };
+        resume_callout_panic();
+
+        search_result(r)
     }
 
     /// Match string and store search result into region object.
@@ -225,7 +453,42 @@ impl Regex {
                              region: &mut Region,
                              options: Options)
                              -> Result<Option<usize>, Error> {
-        let text_bytes = text.as_bytes();
+        self.match_at(text, 0, region, options)
+    }
+
+    /// Match string at byte offset `pos` and store search result into region
+    /// object.
+    ///
+    /// The match is anchored at `pos`, but the engine still sees the whole
+    /// `text`, so lookbehind, `\b` and `^` take the preceding text into
+    /// account. Returns match length if pattern is found, otherwise return
+    /// `None`. You also can use search time options: `OPTION_NOTBOL` and
+    /// `OPTION_NOTEOL`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pos` is out of bounds or doesn't lie on
+    /// a character boundary of `text`.
+    pub fn match_at(&self,
+                    text: &str,
+                    pos: usize,
+                    region: &mut Region,
+                    options: Options)
+                    -> Result<Option<usize>, Error> {
+        if !text.is_char_boundary(pos) {
+            panic!("match position is not on a character boundary")
+        }
+        self.match_raw(text.as_bytes(), pos, region, options)
+    }
+
+    /// Match a byte string at offset `pos`. The offset is only checked to be
+    /// in bounds.
+    pub(crate) fn match_raw(&self,
+                            text_bytes: &[u8],
+                            pos: usize,
+                            region: &mut Region,
+                            options: Options)
+                            -> Result<Option<usize>, Error> {
         let (start, end) = (
             text_bytes.as_ptr(),
             text_bytes[text_bytes.len()..].as_ptr()
@@ -233,81 +496,1146 @@ impl Regex {
 
         let r = unsafe {
             onig_match(
-                self.raw,
+                self.inner.raw,
                 start,
                 end,
-                start,
+                text_bytes[pos..].as_ptr(),
                 region,
-                options.bits()
+                search_options(options)
             )
         };
+        resume_callout_panic();
 
-        if r >= 0 {
-            Ok(Some(r as usize))
-        } else if r == -1 {
-            Ok(None)
-        } else {
-            Err(Error::new(r, None))
+        search_result(r)
+    }
+
+    /// Search pattern in string between byte offsets `start` and `range`,
+    /// like `search_in`, bounding the work with the limits of `param`.
+    ///
+    /// If a limit is hit, an error for which `Error::is_limit_exceeded`
+    /// returns true is returned.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `start` or `range` is out of bounds or doesn't
+    /// lie on a character boundary of `text`.
+    pub fn search_with_param(&self,
+                             text: &str,
+                             start: usize,
+                             range: usize,
+                             region: &mut Region,
+                             options: Options,
+                             param: &MatchParam)
+                             -> Result<Option<usize>, Error> {
+        if !text.is_char_boundary(start) || !text.is_char_boundary(range) {
+            panic!("search window is not on a character boundary")
+        }
+        let text_bytes = text.as_bytes();
+        let (beg, end) = (
+            text_bytes.as_ptr(),
+            text_bytes[text_bytes.len()..].as_ptr()
+        );
+
+        let r = unsafe {
+            onig_search_with_param(
+                self.inner.raw,
+                beg,
+                end,
+                text_bytes[start..].as_ptr(),
+                text_bytes[range..].as_ptr(),
+                region,
+                search_options(options),
+                param.as_raw()
+            )
+        };
+        param.record_search(self, r);
+        resume_callout_panic();
+
+        search_result(r)
+    }
+
+    /// Match string at byte offset `pos`, like `match_at`, bounding the work
+    /// with the limits of `param`.
+    ///
+    /// If a limit is hit, an error for which `Error::is_limit_exceeded`
+    /// returns true is returned.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pos` is out of bounds or doesn't lie on
+    /// a character boundary of `text`.
+    pub fn match_with_param(&self,
+                            text: &str,
+                            pos: usize,
+                            region: &mut Region,
+                            options: Options,
+                            param: &MatchParam)
+                            -> Result<Option<usize>, Error> {
+        if !text.is_char_boundary(pos) {
+            panic!("match position is not on a character boundary")
         }
+        let text_bytes = text.as_bytes();
+        let (start, end) = (
+            text_bytes.as_ptr(),
+            text_bytes[text_bytes.len()..].as_ptr()
+        );
+
+        let r = unsafe {
+            onig_match_with_param(
+                self.inner.raw,
+                start,
+                end,
+                text_bytes[pos..].as_ptr(),
+                region,
+                search_options(options),
+                param.as_raw()
+            )
+        };
+        param.record_search(self, r);
+        resume_callout_panic();
+
+        search_result(r)
     }
 
-    /// Returns true if and only if the regex matches the string given.
+    /// Search pattern in string and store search result into POSIX region
+    /// object, which is grown to fit all capture groups if necessary.
+    ///
+    /// Returns match position offset if pattern is found, otherwise return
+    /// `None`. `OPTION_POSIX_REGION` is always set.
+    pub fn search_with_posix_region(&self,
+                                    text: &str,
+                                    region: &mut PosixRegion,
+                                    options: Options)
+                                    -> Result<Option<usize>, Error> {
+        let text_bytes = text.as_bytes();
+        let (start, end) = (
+            text_bytes.as_ptr(),
+            text_bytes[text_bytes.len()..].as_ptr()
+        );
+
+        let r = unsafe {
+            onig_search(
+                self.inner.raw,
+                start,
+                end,
+                start,
+                end,
+                region.prepare(self.captures_len() + 1),
+                search_options(options) | OPTION_POSIX_REGION.bits()
+            )
+        };
+        resume_callout_panic();
+
+        search_result(r)
+    }
+
+    /// Match string and store search result into POSIX region object, which
+    /// is grown to fit all capture groups if necessary.
+    ///
+    /// Returns match length if pattern is found, otherwise return `None`.
+    /// `OPTION_POSIX_REGION` is always set.
+    pub fn match_with_posix_region(&self,
+                                   text: &str,
+                                   region: &mut PosixRegion,
+                                   options: Options)
+                                   -> Result<Option<usize>, Error> {
+        let text_bytes = text.as_bytes();
+        let (start, end) = (
+            text_bytes.as_ptr(),
+            text_bytes[text_bytes.len()..].as_ptr()
+        );
+
+        let r = unsafe {
+            onig_match(
+                self.inner.raw,
+                start,
+                end,
+                start,
+                region.prepare(self.captures_len() + 1),
+                search_options(options) | OPTION_POSIX_REGION.bits()
+            )
+        };
+        resume_callout_panic();
+
+        search_result(r)
+    }
+
+    /// Returns true if and only if the match found at the start of the string
+    /// covers the whole string.
     ///
     /// # Panics
     ///
     /// This method may panic in the case of memory overflow during execution or
     /// other internal errors of Oniguruma engine.
+    #[deprecated(since = "0.3.2",
+                 note = "`is_match` will search anywhere in the string in 0.4; use \
+                         `is_full_match` to match the whole string or `is_search_match`")]
+    #[allow(deprecated)]
     pub fn is_match(&self, text: &str) -> bool {
+        self.try_is_match(text).unwrap()
+    }
+
+    /// Returns true if and only if the match found at the start of the string
+    /// covers the whole string.
+    ///
+    /// Unlike `is_match`, errors of Oniguruma engine are returned instead of
+    /// causing a panic.
+    #[deprecated(since = "0.3.2",
+                 note = "`try_is_match` will search anywhere in the string in 0.4; use \
+                         `try_is_full_match` to match the whole string or `try_is_search_match`")]
+    pub fn try_is_match(&self, text: &str) -> Result<bool, Error> {
+        let mut region = Region::new();
+        self.match_with_region(text, &mut region, OPTION_NONE)
+            .map(|r| r == Some(text.len()))
+    }
+
+    /// Returns true if and only if the regex matches anywhere in the string
+    /// given.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn is_search_match(&self, text: &str) -> bool {
+        self.try_is_search_match(text).unwrap()
+    }
+
+    /// Returns true if and only if the regex matches anywhere in the string
+    /// given, returning errors of Oniguruma engine instead of panicking.
+    pub fn try_is_search_match(&self, text: &str) -> Result<bool, Error> {
+        let mut region = Region::new();
+        self.search_with_region(text, &mut region, OPTION_NONE)
+            .map(|r| r.is_some())
+    }
+
+    /// Returns true if and only if the regex matches at the start of the
+    /// string given. The match doesn't need to cover the whole string.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn is_prefix_match(&self, text: &str) -> bool {
+        self.try_is_prefix_match(text).unwrap()
+    }
+
+    /// Returns true if and only if the regex matches at the start of the
+    /// string given, returning errors of Oniguruma engine instead of
+    /// panicking.
+    pub fn try_is_prefix_match(&self, text: &str) -> Result<bool, Error> {
         let mut region = Region::new();
         self.match_with_region(text, &mut region, OPTION_NONE)
-            .unwrap()
-            .map(|r| r == text.len())
-            .unwrap_or(false)
+            .map(|r| r.is_some())
     }
 
-    /// Returns the start and end byte range of the leftmost-first match in
-    /// `text`. If no match exists, then `None` is returned.
+    /// Returns true if and only if the regex can match the whole string.
+    ///
+    /// Every way the regex can match at the start of the string is tried, so
+    /// `a|ab` is a full match of `"ab"` even though `find` stops at `"a"`.
+    ///
+    /// The check stops at the first match covering the string, but the
+    /// shorter matches found before it are enumerated like with `each_match`,
+    /// the engine backtracking into each of them. With heavily backtracking
+    /// patterns this can take exponential time, and callouts run again on
+    /// every path tried, including contents callouts set with
+    /// `set_contents_callout`. Checking a pattern ending with `\z` with
+    /// `is_prefix_match` avoids both.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn is_full_match(&self, text: &str) -> bool {
+        self.try_is_full_match(text).unwrap()
+    }
+
+    /// Returns true if and only if the regex can match the whole string,
+    /// returning errors of Oniguruma engine instead of panicking.
+    pub fn try_is_full_match(&self, text: &str) -> Result<bool, Error> {
+        let mut full = false;
+        self.each_match_at(text, Some(0), OPTION_NONE, &mut |_, region| {
+            if region.pos(0).map(|(_, end)| end) == Some(text.len()) {
+                full = true;
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }).map(|_| full)
+    }
+
+    /// Returns the leftmost-first match in `text`. If no match exists, then
+    /// `None` is returned.
     ///
     /// Note that this should only be used if you want to discover the position
     /// of the match. Testing the existence of a match is faster if you use
-    /// `is_match`.
+    /// `is_search_match`.
     ///
     /// # Panics
     ///
     /// This method may panic in the case of memory overflow during execution or
     /// other internal errors of Oniguruma engine.
-    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+    pub fn find<'t>(&self, text: &'t str) -> Option<Match<'t>> {
+        self.try_find(text).unwrap()
+    }
+
+    /// Returns the leftmost-first match in `text`. If no match exists, then
+    /// `Ok(None)` is returned.
+    ///
+    /// Unlike `find`, errors of Oniguruma engine are returned instead of
+    /// causing a panic.
+    pub fn try_find<'t>(&self, text: &'t str) -> Result<Option<Match<'t>>, Error> {
         let mut region = Region::new();
         self.search_with_region(text, &mut region, OPTION_NONE)
-            .unwrap()
-            .map(|_| region.pos(0))
-            .unwrap_or(None)
+            .map(|r| r.and_then(|_| region.pos(0)))
+            .map(|pos| pos.map(|(beg, end)| Match::new(text, beg, end)))
+    }
+
+    /// Returns an iterator for each successive non-overlapping match in
+    /// `text`.
+    ///
+    /// After an empty match the search is resumed one character further, so
+    /// the iterator always makes progress.
+    ///
+    /// # Panics
+    ///
+    /// The iterator may panic in the case of memory overflow during execution
+    /// or other internal errors of Oniguruma engine.
+    pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> FindMatches<'r, 't> {
+        FindMatches {
+            inner: Successive::new(self, text, true),
+            region: Region::new()
+        }
+    }
+
+    /// Returns an iterator for each successive non-overlapping match in
+    /// `text`, like `find_iter`, but yields errors of Oniguruma engine instead
+    /// of panicking. The iterator stops after the first error.
+    pub fn try_find_iter<'r, 't>(&'r self, text: &'t str) -> TryFindMatches<'r, 't> {
+        TryFindMatches { inner: self.find_iter(text) }
+    }
+
+    /// Returns the rightmost match in `text`, found by searching backward from
+    /// the end of the string. If no match exists, then `None` is returned.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn rfind<'t>(&self, text: &'t str) -> Option<Match<'t>> {
+        self.rfind_at(text, text.len())
+    }
+
+    /// Returns the rightmost match in `text`, like `rfind`. If no match
+    /// exists, then `Ok(None)` is returned.
+    ///
+    /// Unlike `rfind`, errors of Oniguruma engine are returned instead of
+    /// causing a panic.
+    pub fn try_rfind<'t>(&self, text: &'t str) -> Result<Option<Match<'t>>, Error> {
+        self.try_rfind_at(text, text.len())
+    }
+
+    /// Returns the match with the greatest start position not exceeding
+    /// `pos`. The match itself may extend past `pos`. If no match exists,
+    /// then `None` is returned.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pos` doesn't lie on a character boundary of
+    /// `text`. It also may panic in the case of memory overflow during
+    /// execution or other internal errors of Oniguruma engine.
+    pub fn rfind_at<'t>(&self, text: &'t str, pos: usize) -> Option<Match<'t>> {
+        self.try_rfind_at(text, pos).unwrap()
+    }
+
+    /// Returns the match with the greatest start position not exceeding
+    /// `pos`, like `rfind_at`. If no match exists, then `Ok(None)` is
+    /// returned.
+    ///
+    /// Unlike `rfind_at`, errors of Oniguruma engine are returned instead of
+    /// causing a panic.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `pos` doesn't lie on a character boundary of
+    /// `text`.
+    pub fn try_rfind_at<'t>(&self, text: &'t str, pos: usize) -> Result<Option<Match<'t>>, Error> {
+        let mut region = Region::new();
+        self.search_in(text, pos, 0, &mut region, OPTION_NONE)
+            .map(|r| r.and_then(|_| region.pos(0)))
+            .map(|pos| pos.map(|(beg, end)| Match::new(text, beg, end)))
+    }
+
+    /// Returns an iterator over matches in `text` from the end toward the
+    /// beginning. Each match starts strictly before the previous one, but
+    /// unlike `find_iter` the matches may overlap.
+    ///
+    /// # Panics
+    ///
+    /// The iterator may panic in the case of memory overflow during execution
+    /// or other internal errors of Oniguruma engine.
+    pub fn rfind_iter<'r, 't>(&'r self, text: &'t str) -> RFindMatches<'r, 't> {
+        RFindMatches {
+            regex: self,
+            text: text,
+            region: Region::new(),
+            next_start: Some(text.len())
+        }
+    }
+
+    /// Returns an iterator over matches in `text` from the end toward the
+    /// beginning, like `rfind_iter`, but yields errors of Oniguruma engine
+    /// instead of panicking. The iterator stops after the first error.
+    pub fn try_rfind_iter<'r, 't>(&'r self, text: &'t str) -> TryRFindMatches<'r, 't> {
+        TryRFindMatches { inner: self.rfind_iter(text) }
+    }
+
+    /// Calls `callback` for each successive non-overlapping match in `text`,
+    /// letting Oniguruma drive the whole loop in a single call.
+    ///
+    /// The callback receives the index of the match, the byte position where
+    /// it starts and the region holding its capture groups, and returns
+    /// `ControlFlow::Break` to stop scanning early. Returns the number of
+    /// matches passed to the callback. A panic in the callback stops the scan
+    /// and is resumed once control is back in Rust.
+    pub fn scan<F>(&self, text: &str, options: Options, callback: F) -> Result<usize, Error>
+        where F: FnMut(usize, usize, &Region) -> ControlFlow<()>
+    {
+        let text_bytes = text.as_bytes();
+        let (start, end) = (
+            text_bytes.as_ptr(),
+            text_bytes[text_bytes.len()..].as_ptr()
+        );
+        let mut region = Region::new();
+        let mut state = ScanState { callback: callback, count: 0, panic: None };
+
+        let r = unsafe {
+            onig_scan(
+                self.inner.raw,
+                start,
+                end,
+                &mut region,
+                search_options(options),
+                scan_callback::<F>,
+                &mut state as *mut ScanState<F> as *mut c_void
+            )
+        };
+        resume_callout_panic();
+
+        if let Some(payload) = state.panic {
+            panic::resume_unwind(payload)
+        }
+        if r >= 0 {
+            Ok(state.count)
+        } else {
+            Err(Error::new(r, None))
+        }
+    }
+
+    /// Calls `callback` for every way the regular expression can match in
+    /// `text`, not just the first one found at each position.
+    ///
+    /// The callback receives the byte position where the match starts and
+    /// the region holding the groups of that match path, and returns
+    /// `ControlFlow::Break` to stop the enumeration early. Returns the number
+    /// of match paths passed to the callback. A panic in the callback stops
+    /// the search and is resumed once control is back in Rust.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use oniguruma::{Regex, OPTION_NONE};
+    ///
+    /// let r = Regex::new("a+").unwrap();
+    /// let mut found = Vec::new();
+    /// r.each_match("aa", OPTION_NONE, |_, region| {
+    ///     found.push(region.pos(0).unwrap());
+    ///     ControlFlow::Continue(())
+    /// }).unwrap();
+    /// assert_eq!(found, vec![(0, 2), (0, 1), (1, 2)]);
+    /// ```
+    pub fn each_match<F>(&self, text: &str, options: Options, mut callback: F) -> Result<usize, Error>
+        where F: FnMut(usize, &Region) -> ControlFlow<()>
+    {
+        self.each_match_at(text, None, options, &mut callback)
+    }
+
+    /// Reports match paths to `callback` like `each_match`, only for matches
+    /// starting at byte offset `at` if given.
+    fn each_match_at(&self,
+                     text: &str,
+                     at: Option<usize>,
+                     options: Options,
+                     callback: &mut dyn FnMut(usize, &Region) -> ControlFlow<()>)
+                     -> Result<usize, Error> {
+        INSTALL_EACH_MATCH_CALLBACK.call_once(|| unsafe {
+            onig_set_callback_each_match(each_match_callback);
+        });
+        let text_bytes = text.as_bytes();
+        let (start, end) = (
+            text_bytes.as_ptr(),
+            text_bytes[text_bytes.len()..].as_ptr()
+        );
+        let mut region = Region::new();
+        let mut state = EachMatchState {
+            text: start,
+            callback: callback,
+            count: 0,
+            panic: None
+        };
+        let mut param = MatchParam::new();
+        param.set_callout_user_data(&mut state as *mut EachMatchState as *mut c_void);
+
+        let options = (options & !OPTION_POSIX_REGION) | OPTION_CALLBACK_EACH_MATCH;
+
+        let r = unsafe {
+            match at {
+                Some(pos) => onig_match_with_param(
+                    self.inner.raw,
+                    start,
+                    end,
+                    text_bytes[pos..].as_ptr(),
+                    &mut region,
+                    options.bits(),
+                    param.as_raw()
+                ),
+                None => onig_search_with_param(
+                    self.inner.raw,
+                    start,
+                    end,
+                    start,
+                    end,
+                    &mut region,
+                    options.bits(),
+                    param.as_raw()
+                )
+            }
+        };
+        resume_callout_panic();
+
+        if let Some(payload) = state.panic.take() {
+            panic::resume_unwind(payload)
+        }
+        if r >= 0 || r == ONIG_MISMATCH || r == ONIG_ABORT {
+            Ok(state.count)
+        } else {
+            Err(Error::new(r, None))
+        }
+    }
+
+    /// Returns an iterator of substrings of `text` delimited by a match of the
+    /// regular expression. Namely, each element of the iterator corresponds to
+    /// text that *isn't* matched by the regular expression.
+    ///
+    /// Call `with_captures` on the returned iterator to also yield captured
+    /// parts of delimiters, as Ruby's `String#split` does.
+    ///
+    /// # Panics
+    ///
+    /// The iterator may panic in the case of memory overflow during execution
+    /// or other internal errors of Oniguruma engine.
+    pub fn split<'r, 't>(&'r self, text: &'t str) -> RegexSplits<'r, 't> {
+        RegexSplits {
+            finder: self.captures_iter(text),
+            text: text,
+            last: 0,
+            limit: None,
+            with_captures: false,
+            pending: None,
+            done: false
+        }
+    }
+
+    /// Returns an iterator of at most `limit` substrings of `text` delimited
+    /// by a match of the regular expression. The last element contains the
+    /// remainder of `text`. Captured delimiters yielded in `with_captures`
+    /// mode are not counted against the limit.
+    ///
+    /// # Panics
+    ///
+    /// The iterator may panic in the case of memory overflow during execution
+    /// or other internal errors of Oniguruma engine.
+    pub fn splitn<'r, 't>(&'r self, text: &'t str, limit: usize) -> RegexSplits<'r, 't> {
+        RegexSplits { limit: Some(limit), ..self.split(text) }
+    }
+
+    /// Returns an iterator of substrings of `text` delimited by a match of the
+    /// regular expression, like `split`, but yields errors of Oniguruma engine
+    /// instead of panicking. The iterator stops after the first error.
+    pub fn try_split<'r, 't>(&'r self, text: &'t str) -> TryRegexSplits<'r, 't> {
+        TryRegexSplits { inner: self.split(text) }
+    }
+
+    /// Returns an iterator of at most `limit` substrings of `text` delimited
+    /// by a match of the regular expression, like `splitn`, but yields errors
+    /// of Oniguruma engine instead of panicking. The iterator stops after the
+    /// first error.
+    pub fn try_splitn<'r, 't>(&'r self, text: &'t str, limit: usize) -> TryRegexSplits<'r, 't> {
+        TryRegexSplits { inner: self.splitn(text, limit) }
+    }
+
+    /// Returns the number of the group with the given name. If several groups
+    /// share the name, the last one that matched in `region` is returned.
+    pub(crate) fn name_to_backref_number(&self,
+                                         name: &str,
+                                         region: Option<&Region>)
+                                         -> Option<usize> {
+        let name_bytes = name.as_bytes();
+        let r = unsafe {
+            onig_name_to_backref_number(
+                self.inner.raw,
+                name_bytes.as_ptr(),
+                name_bytes[name_bytes.len()..].as_ptr(),
+                region.map(|r| r as *const Region).unwrap_or(ptr::null())
+            )
+        };
+        if r > 0 {
+            Some(r as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the numbers of all groups with the given name in order of
+    /// appearance in the regular expression. The result is empty if there is
+    /// no such group.
+    pub fn name_to_group_numbers(&self, name: &str) -> Vec<usize> {
+        let name_bytes = name.as_bytes();
+        let mut nums: *const c_int = ptr::null();
+        let n = unsafe {
+            onig_name_to_group_numbers(
+                self.inner.raw,
+                name_bytes.as_ptr(),
+                name_bytes[name_bytes.len()..].as_ptr(),
+                &mut nums
+            )
+        };
+        if n <= 0 {
+            return Vec::new()
+        }
+        unsafe {
+            slice::from_raw_parts(nums, n as usize).iter().map(|&g| g as usize).collect()
+        }
+    }
+
+    /// Returns the number of the callout tagged `tag`, e.g. `(*COUNT[tag])`,
+    /// or `None` if the regex has no such tag.
+    pub fn callout_num_by_tag(&self, tag: &str) -> Option<i32> {
+        let tag_bytes = tag.as_bytes();
+        let r = unsafe {
+            onig_get_callout_num_by_tag(
+                self.inner.raw,
+                tag_bytes.as_ptr(),
+                tag_bytes[tag_bytes.len()..].as_ptr()
+            )
+        };
+        if r > 0 {
+            Some(r as i32)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of callouts in the pattern, numbered from 1.
+    ///
+    /// Oniguruma's public API has no way to get it, so this relies on the
+    /// private `onig_reg_callout_list_at`, which returns null for numbers
+    /// outside of `1..=count`.
+    pub(crate) fn callout_count(&self) -> c_int {
+        let mut n = 0;
+        while !unsafe { onig_reg_callout_list_at(self.inner.raw, n + 1) }.is_null() {
+            n += 1;
+        }
+        n
+    }
+
+    pub(crate) fn as_raw(&self) -> OnigRegex {
+        self.inner.raw
+    }
+
+    /// Releases ownership of the compiled Oniguruma object, which must then be
+    /// freed by the caller. Returns `None` if the regex is shared with clones
+    /// or captures.
+    pub(crate) fn into_raw(self) -> Option<OnigRegex> {
+        Rc::try_unwrap(self.inner).ok().map(|mut inner| {
+            mem::replace(&mut inner.raw, ptr::null())
+        })
+    }
+
+    /// Returns names of the groups together with their group numbers, ordered
+    /// by the first group number.
+    pub(crate) fn names(&self) -> &[(String, Vec<usize>)] {
+        &self.inner.names
+    }
+
+    /// Returns an iterator over the names of the capture groups together with
+    /// the numbers of the groups carrying each name. Names are yielded in
+    /// order of appearance in the regular expression.
+    pub fn capture_names<'r>(&'r self) -> CaptureNames<'r> {
+        CaptureNames { iter: self.inner.names.iter() }
+    }
+
+    /// Creates an empty region with room for all capture groups of this
+    /// regex, so that searches storing into it don't need to allocate.
+    pub fn new_region(&self) -> Region {
+        Region::with_capacity(self.captures_len() + 1)
     }
 
     pub fn captures_len(&self) -> usize {
         unsafe {
-            onig_number_of_captures(self.raw) as usize
+            onig_number_of_captures(self.inner.raw) as usize
         }
     }
 
     pub fn capture_histories_len(&self) -> usize {
         unsafe {
-            onig_number_of_capture_histories(self.raw) as usize
+            onig_number_of_capture_histories(self.inner.raw) as usize
         }
     }
 
     pub fn names_len(&self) -> usize {
         unsafe {
-            onig_number_of_names(self.raw) as usize
+            onig_number_of_names(self.inner.raw) as usize
         }
     }
 }
 
-impl Drop for Regex {
+impl Drop for RawRegex {
     fn drop(&mut self) {
+        if self.raw.is_null() {
+            return
+        }
+        remove_regex_callouts(self.raw);
         unsafe {
             onig_free(self.raw);
         }
     }
 }
 
+/// State shared with `scan_callback` during `Regex::scan`.
+struct ScanState<F> {
+    callback: F,
+    count: usize,
+    panic: Option<Box<dyn Any + Send>>
+}
+
+/// Callback for `onig_scan` forwarding matches to the closure stored in the
+/// `ScanState` passed as `arg`. Non-zero return value stops the scan.
+extern "C" fn scan_callback<F>(index: c_int,
+                               pos: c_int,
+                               region: *const Region,
+                               arg: *mut c_void)
+                               -> c_int
+    where F: FnMut(usize, usize, &Region) -> ControlFlow<()>
+{
+    let state = unsafe { &mut *(arg as *mut ScanState<F>) };
+    let region = unsafe { &*region };
+    let result = {
+        let callback = &mut state.callback;
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            callback(index as usize, pos as usize, region)
+        }))
+    };
+    match result {
+        Ok(ControlFlow::Continue(())) => {
+            state.count += 1;
+            0
+        }
+        Ok(ControlFlow::Break(())) => {
+            state.count += 1;
+            1
+        }
+        Err(payload) => {
+            state.panic = Some(payload);
+            1
+        }
+    }
+}
+
+static INSTALL_EACH_MATCH_CALLBACK: Once = Once::new();
+
+/// State shared with `each_match_callback` during `Regex::each_match`.
+struct EachMatchState<'c> {
+    text: *const u8,
+    callback: &'c mut dyn FnMut(usize, &Region) -> ControlFlow<()>,
+    count: usize,
+    panic: Option<Box<dyn Any + Send>>
+}
+
+/// Callback installed with `onig_set_callback_each_match`, forwarding match
+/// paths to the closure of the `EachMatchState` passed as `user_data`.
+/// Returning `ONIG_ABORT` stops the search.
+extern "C" fn each_match_callback(_str: *const u8,
+                                  _end: *const u8,
+                                  match_start: *const u8,
+                                  region: *const Region,
+                                  user_data: *mut c_void)
+                                  -> c_int {
+    if user_data.is_null() {
+        return 0
+    }
+    let state = unsafe { &mut *(user_data as *mut EachMatchState) };
+    let region = unsafe { &*region };
+    let pos = match_start as usize - state.text as usize;
+    let result = {
+        let callback = &mut state.callback;
+        panic::catch_unwind(AssertUnwindSafe(|| callback(pos, region)))
+    };
+    match result {
+        Ok(ControlFlow::Continue(())) => {
+            state.count += 1;
+            0
+        }
+        Ok(ControlFlow::Break(())) => {
+            state.count += 1;
+            ONIG_ABORT
+        }
+        Err(payload) => {
+            state.panic = Some(payload);
+            ONIG_ABORT
+        }
+    }
+}
+
+/// Callback for `onig_foreach_name` collecting names and group numbers into
+/// the vector passed as `arg`.
+extern "C" fn push_name(name: *const u8,
+                        name_end: *const u8,
+                        group_num_len: c_int,
+                        group_nums: *const c_int,
+                        _: OnigRegex,
+                        arg: *mut c_void)
+                        -> c_int {
+    let names = unsafe { &mut *(arg as *mut Vec<(String, Vec<usize>)>) };
+    let (name, groups) = unsafe {
+        (
+            slice::from_raw_parts(name, name_end as usize - name as usize),
+            slice::from_raw_parts(group_nums, group_num_len as usize)
+        )
+    };
+    names.push((
+        String::from_utf8_lossy(name).into_owned(),
+        groups.iter().map(|&g| g as usize).collect()
+    ));
+    0
+}
+
+
+/// An iterator over the names of all capture groups of a regular expression
+/// and the group numbers associated with them.
+///
+/// `'r` is the lifetime of the compiled expression.
+pub struct CaptureNames<'r> {
+    iter: slice::Iter<'r, (String, Vec<usize>)>
+}
+
+impl<'r> iter::Iterator for CaptureNames<'r> {
+    type Item = (&'r str, &'r [usize]);
+
+    fn next(&mut self) -> Option<(&'r str, &'r [usize])> {
+        self.iter.next().map(|&(ref name, ref groups)| (&name[..], &groups[..]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator over all non-overlapping matches for a particular string.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct FindMatches<'r, 't> {
+    inner: Successive<'r, 't, str>,
+    region: Region
+}
+
+impl<'r, 't> FindMatches<'r, 't> {
+    fn try_next(&mut self) -> Result<Option<Match<'t>>, Error> {
+        let text = self.inner.text();
+        self.inner.next_match(&mut self.region)
+            .map(|pos| pos.map(|(beg, end)| Match::new(text, beg, end)))
+    }
+}
+
+impl<'r, 't> iter::Iterator for FindMatches<'r, 't> {
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Match<'t>> {
+        self.try_next().unwrap()
+    }
+}
+
+/// An iterator over all non-overlapping matches for a particular string,
+/// yielding an error instead of panicking if the search fails.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct TryFindMatches<'r, 't> {
+    inner: FindMatches<'r, 't>
+}
+
+impl<'r, 't> iter::Iterator for TryFindMatches<'r, 't> {
+    type Item = Result<Match<'t>, Error>;
+
+    fn next(&mut self) -> Option<Result<Match<'t>, Error>> {
+        match self.inner.try_next() {
+            Ok(Some(pos)) => Some(Ok(pos)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err))
+        }
+    }
+}
+
+/// An iterator over matches for a particular string, walking from the end of
+/// the string toward its beginning.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct RFindMatches<'r, 't> {
+    regex: &'r Regex,
+    text: &'t str,
+    region: Region,
+    next_start: Option<usize>
+}
+
+impl<'r, 't> RFindMatches<'r, 't> {
+    fn try_next(&mut self) -> Result<Option<Match<'t>>, Error> {
+        let start = match self.next_start.take() {
+            Some(start) => start,
+            None => return Ok(None)
+        };
+        let found = self.regex.search_in(self.text, start, 0, &mut self.region, OPTION_NONE)?;
+        let (beg, end) = match found.and_then(|_| self.region.pos(0)) {
+            Some(pos) => pos,
+            None => return Ok(None)
+        };
+        self.next_start = if beg > 0 {
+            Some(beg - prev_char_len(self.text, beg))
+        } else {
+            None
+        };
+        Ok(Some(Match::new(self.text, beg, end)))
+    }
+}
+
+impl<'r, 't> iter::Iterator for RFindMatches<'r, 't> {
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Match<'t>> {
+        self.try_next().unwrap()
+    }
+}
+
+/// An iterator over matches for a particular string, walking from the end of
+/// the string toward its beginning, yielding an error instead of panicking if
+/// the search fails.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub struct TryRFindMatches<'r, 't> {
+    inner: RFindMatches<'r, 't>
+}
+
+impl<'r, 't> iter::Iterator for TryRFindMatches<'r, 't> {
+    type Item = Result<Match<'t>, Error>;
+
+    fn next(&mut self) -> Option<Result<Match<'t>, Error>> {
+        match self.inner.try_next() {
+            Ok(Some(pos)) => Some(Ok(pos)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err))
+        }
+    }
+}
+
+/// Yields all substrings delimited by a regular expression match.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the string being split.
+pub struct RegexSplits<'r, 't> {
+    finder: FindCaptures<'r, 't>,
+    text: &'t str,
+    last: usize,
+    limit: Option<usize>,
+    with_captures: bool,
+    pending: Option<(Captures<'t>, usize)>,
+    done: bool
+}
+
+impl<'r, 't> RegexSplits<'r, 't> {
+    /// Also yield the text of every capture group that participated in a
+    /// delimiting match, right after the substring preceding it.
+    pub fn with_captures(mut self) -> RegexSplits<'r, 't> {
+        self.with_captures = true;
+        self
+    }
+}
+
+impl<'r, 't> RegexSplits<'r, 't> {
+    fn try_next(&mut self) -> Result<Option<&'t str>, Error> {
+        if let Some((caps, mut idx)) = self.pending.take() {
+            while idx < caps.len() {
+                idx += 1;
+                if let Some(s) = caps.at(idx - 1) {
+                    self.pending = Some((caps, idx));
+                    return Ok(Some(s))
+                }
+            }
+        }
+        if self.done {
+            return Ok(None)
+        }
+        if let Some(limit) = self.limit {
+            if limit == 0 {
+                self.done = true;
+                return Ok(None)
+            }
+            self.limit = Some(limit - 1);
+            if limit == 1 {
+                self.done = true;
+                return Ok(Some(&self.text[self.last..]))
+            }
+        }
+        let found = self.finder.try_next();
+        match found {
+            Ok(Some(caps)) => {
+                let (beg, end) = caps.pos(0).unwrap();
+                let piece = &self.text[self.last..beg];
+                self.last = end;
+                if self.with_captures {
+                    self.pending = Some((caps, 1));
+                }
+                Ok(Some(piece))
+            }
+            Ok(None) => {
+                self.done = true;
+                Ok(Some(&self.text[self.last..]))
+            }
+            Err(err) => {
+                self.done = true;
+                Err(err)
+            }
+        }
+    }
+}
+
+impl<'r, 't> iter::Iterator for RegexSplits<'r, 't> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<&'t str> {
+        self.try_next().unwrap()
+    }
+}
+
+/// Yields all substrings delimited by a regular expression match, yielding
+/// an error instead of panicking if the search fails.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the string being split.
+pub struct TryRegexSplits<'r, 't> {
+    inner: RegexSplits<'r, 't>
+}
+
+impl<'r, 't> TryRegexSplits<'r, 't> {
+    /// Also yield the text of every capture group that participated in a
+    /// delimiting match, like `RegexSplits::with_captures`.
+    pub fn with_captures(self) -> TryRegexSplits<'r, 't> {
+        TryRegexSplits { inner: self.inner.with_captures() }
+    }
+}
+
+impl<'r, 't> iter::Iterator for TryRegexSplits<'r, 't> {
+    type Item = Result<&'t str, Error>;
+
+    fn next(&mut self) -> Option<Result<&'t str, Error>> {
+        match self.inner.try_next() {
+            Ok(Some(piece)) => Some(Ok(piece)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err))
+        }
+    }
+}
+
+/// Search state of the iterators over successive non-overlapping matches,
+/// shared by `&str` and byte string haystacks.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched string.
+pub(crate) struct Successive<'r, 't, T: ?Sized + 't> {
+    regex: &'r Regex,
+    text: &'t T,
+    utf8: bool,
+    last_end: usize
+}
+
+impl<'r, 't, T: ?Sized + AsRef<[u8]>> Successive<'r, 't, T> {
+    /// Starts at the beginning of `text`, which is stepped over by UTF-8
+    /// characters after empty matches if `utf8` is true, by bytes otherwise.
+    pub(crate) fn new(regex: &'r Regex, text: &'t T, utf8: bool) -> Successive<'r, 't, T> {
+        Successive { regex: regex, text: text, utf8: utf8, last_end: 0 }
+    }
+
+    pub(crate) fn regex(&self) -> &'r Regex {
+        self.regex
+    }
+
+    pub(crate) fn text(&self) -> &'t T {
+        self.text
+    }
+
+    /// Searches the next match into `region` and returns its position.
+    ///
+    /// After an empty match the search is resumed one character further, so
+    /// that it always makes progress. Nothing is found anymore after the last
+    /// match or an error.
+    pub(crate) fn next_match(&mut self, region: &mut Region) -> Result<Option<(usize, usize)>, Error> {
+        let text = self.text.as_ref();
+        if self.last_end > text.len() {
+            return Ok(None)
+        }
+        let found = self.regex.search_raw(text, self.last_end, text.len(), region, OPTION_NONE);
+        let (beg, end) = match found.map(|r| r.and_then(|_| region.pos(0))) {
+            Ok(Some(pos)) => pos,
+            Ok(None) => {
+                self.last_end = text.len() + 1;
+                return Ok(None)
+            }
+            Err(err) => {
+                self.last_end = text.len() + 1;
+                return Err(err)
+            }
+        };
+        self.last_end = if beg == end {
+            end + next_char_len(text, end, self.utf8)
+        } else {
+            end
+        };
+        Ok(Some((beg, end)))
+    }
+}
+
+/// Returns byte length of the character starting at `pos`, or `1` if `pos`
+/// is the end of the string. Every byte is a character unless `utf8` is true.
+fn next_char_len(text: &[u8], pos: usize, utf8: bool) -> usize {
+    let lead = match text.get(pos) {
+        Some(&lead) if utf8 => lead,
+        _ => return 1
+    };
+    let len = if lead < 0xc0 {
+        1
+    } else if lead < 0xe0 {
+        2
+    } else if lead < 0xf0 {
+        3
+    } else {
+        4
+    };
+    len.min(text.len() - pos)
+}
+
+/// Returns byte length of the character ending at `pos`, or `1` if `pos` is
+/// the start of the string.
+fn prev_char_len(text: &str, pos: usize) -> usize {
+    text[..pos].chars().next_back().map(|c| c.len_utf8()).unwrap_or(1)
+}
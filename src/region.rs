@@ -1,15 +1,16 @@
 use libc::c_int;
-use std::{iter, ops, mem, ptr};
+use std::{iter, ops, ptr};
 
 #[link(name="onig")]
 extern {
-    // TODO: shoud we use original malloc memory managment for regions?
-    // fn onig_region_new() -> *const Region;
-    // fn onig_region_free(region: *const Region, free_self: c_int);
-    fn onig_region_clear(region: *const Region);
+    fn onig_region_free(region: *mut Region, free_self: c_int);
+    fn onig_region_copy(to: *mut Region, from: *const Region);
+    fn onig_region_resize(region: *mut Region, n: c_int) -> c_int;
+    fn onig_region_clear(region: *mut Region);
     fn onig_get_capture_tree(region: *const Region) -> *const CaptureTreeNode;
 }
 
+/// Node of the capture history tree, borrowed from a `Region`.
 #[repr(C)]
 #[derive(Debug)]
 pub struct CaptureTreeNode {
@@ -44,6 +45,31 @@ impl Region {
         }
     }
 
+    /// Create region with room for `n` capture groups, so that searches with
+    /// at most `n` groups don't need to allocate. All groups are initially
+    /// unmatched.
+    pub fn with_capacity(n: usize) -> Region {
+        let mut region = Region::new();
+        region.resize(n);
+        region
+    }
+
+    /// Resize region to hold `n` capture groups and mark all of them as
+    /// unmatched. Memory is only reallocated if the region grows.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if Oniguruma fails to allocate memory.
+    pub fn resize(&mut self, n: usize) {
+        let r = unsafe {
+            onig_region_resize(self, n as c_int)
+        };
+        if r != 0 {
+            panic!("failed to resize region")
+        }
+        self.clear();
+    }
+
     /// Returns the number of captured groups.
     pub fn len(&self) -> usize {
         self.num_regs as usize
@@ -70,16 +96,17 @@ impl Region {
         }
     }
 
-    pub fn tree(&self) -> Option<&CaptureTreeNode> {
+    /// Returns the root of the capture history tree, or `None` if the regex
+    /// has no capture history groups `(?@...)`. The tree lives as long as
+    /// the region isn't modified; use `CaptureTree` to keep a copy.
+    pub fn tree<'r>(&'r self) -> Option<&'r CaptureTreeNode> {
         let raw = unsafe {
             onig_get_capture_tree(self)
         };
         if raw.is_null() {
             None
         } else {
-            Some(unsafe {
-                mem::transmute(raw)
-            })
+            Some(unsafe { &*raw })
         }
     }
 
@@ -89,6 +116,119 @@ impl Region {
             onig_region_clear(self);
         }
     }
+
+    /// Creates an iterator of the positions of all capture groups. Groups
+    /// that did not match anything are yielded as `None`.
+    pub fn iter<'r>(&'r self) -> RegionIter<'r> {
+        RegionIter { idx: 0, region: self }
+    }
+}
+
+impl Clone for Region {
+    fn clone(&self) -> Region {
+        let mut region = Region::new();
+        unsafe {
+            onig_region_copy(&mut region, self);
+        }
+        region
+    }
+
+    fn clone_from(&mut self, source: &Region) {
+        unsafe {
+            onig_region_copy(self, source)
+        }
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        // The region itself is owned by Rust, only its arrays and capture
+        // history are allocated by Oniguruma.
+        unsafe {
+            onig_region_free(self, 0);
+        }
+    }
+}
+
+/// An iterator over capture group positions of a region.
+///
+/// `'r` is the lifetime of the region.
+#[derive(Debug)]
+pub struct RegionIter<'r> {
+    idx: usize,
+    region: &'r Region
+}
+
+impl<'r> iter::Iterator for RegionIter<'r> {
+    type Item = Option<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Option<(usize, usize)>> {
+        if self.idx < self.region.len() {
+            self.idx += 1;
+            Some(self.region.pos(self.idx - 1))
+        } else {
+            None
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PosixMatch {
+    rm_so: c_int,
+    rm_eo: c_int
+}
+
+/// Representation of regex search result in the POSIX `regmatch_t` layout.
+///
+/// Unlike `Region`, it is a plain array owned by Rust and never reallocated by
+/// Oniguruma. It is filled by `Regex::search_with_posix_region` and
+/// `Regex::match_with_posix_region`.
+#[derive(Clone, Debug)]
+pub struct PosixRegion {
+    regs: Vec<PosixMatch>
+}
+
+impl PosixRegion {
+    /// Create region with room for `n` capture groups.
+    pub fn with_capacity(n: usize) -> PosixRegion {
+        PosixRegion {
+            regs: vec![PosixMatch { rm_so: -1, rm_eo: -1 }; n]
+        }
+    }
+
+    /// Returns the number of capture groups the region has room for.
+    pub fn len(&self) -> usize {
+        self.regs.len()
+    }
+
+    /// Returns true if and only if the region has no room for capture groups.
+    pub fn is_empty(&self) -> bool {
+        self.regs.is_empty()
+    }
+
+    /// Returns the start and end positions of the Nth capture group. Returns
+    /// `None` if i is not a valid capture group or if the capture group did
+    /// not match anything.
+    pub fn pos(&self, pos: usize) -> Option<(usize, usize)> {
+        match self.regs.get(pos) {
+            Some(reg) if reg.rm_so >= 0 => Some((reg.rm_so as usize, reg.rm_eo as usize)),
+            _ => None
+        }
+    }
+
+    /// Grows the region to at least `n` capture groups and marks all of them
+    /// as unmatched. Returns a pointer suitable for Oniguruma search functions.
+    pub(crate) fn prepare(&mut self, n: usize) -> *mut Region {
+        if self.regs.len() < n {
+            self.regs.resize(n, PosixMatch { rm_so: -1, rm_eo: -1 });
+        }
+        for reg in self.regs.iter_mut() {
+            reg.rm_so = -1;
+            reg.rm_eo = -1;
+        }
+        self.regs.as_mut_ptr() as *mut Region
+    }
 }
 
 impl CaptureTreeNode {
@@ -117,7 +257,7 @@ impl ops::Index<usize> for CaptureTreeNode {
             panic!("capture tree node index overflow")
         }
         unsafe {
-            mem::transmute(*self.childs.offset(index as isize))
+            &**self.childs.offset(index as isize)
         }
     }
 }
@@ -0,0 +1,206 @@
+use libc::{c_int, c_uint, c_void};
+use std::ptr;
+use super::{Error, Options, Regex, RegexConfig, Region, ENCODING_UTF8, OPTION_NONE};
+use super::regex::{search_options, search_result, OnigRegex};
+use super::callout::resume_callout_panic;
+
+type OnigRegSet = *const c_void;
+
+#[link(name="onig")]
+extern {
+    fn onig_regset_new(rset: *mut OnigRegSet, n: c_int, regs: *const OnigRegex) -> c_int;
+    fn onig_regset_add(set: OnigRegSet, reg: OnigRegex) -> c_int;
+    fn onig_regset_free(set: OnigRegSet);
+    fn onig_regset_number_of_regex(set: OnigRegSet) -> c_int;
+    fn onig_regset_get_region(set: OnigRegSet, at: c_int) -> *const Region;
+
+    fn onig_regset_search(
+        set: OnigRegSet,
+        str: *const u8,
+        end: *const u8,
+        start: *const u8,
+        range: *const u8,
+        lead: c_int,
+        option: c_uint,
+        rmatch_pos: *mut c_int
+    ) -> c_int;
+
+    fn onig_free(reg: OnigRegex);
+}
+
+/// Strategy used by `RegexSet` to pick a match when several regexes match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegSetLead {
+    /// The match starting at the leftmost position wins. At the same position
+    /// the regex added first wins.
+    PositionLead = 0,
+    /// Like `PositionLead`, but each position is tried with all regexes in
+    /// turn, which is faster for many short patterns.
+    RegexLead = 1,
+    /// The regex added first wins if it matches anywhere, regardless of the
+    /// match position.
+    PriorityToRegexOrder = 2
+}
+
+/// A set of compiled regular expressions searched together in a single pass.
+#[derive(Debug)]
+pub struct RegexSet {
+    raw: OnigRegSet
+}
+
+impl RegexSet {
+    /// Compiles a set of regular expressions with default options. Default
+    /// syntax is `SYNTAX_RUBY`.
+    pub fn new(patterns: &[&str]) -> Result<RegexSet, Error> {
+        RegexSet::new_with_config(patterns, RegexConfig::default())
+    }
+
+    /// Compiles a set of regular expressions with the same config. Note that
+    /// `OPTION_FIND_LONGEST` is not allowed in a set.
+    pub fn new_with_config<'a>(patterns: &[&str],
+                               config: RegexConfig<'a>)
+                               -> Result<RegexSet, Error> {
+        let mut raw: OnigRegSet = ptr::null();
+        let err = unsafe {
+            onig_regset_new(&mut raw, 0, ptr::null())
+        };
+        if err != 0 {
+            return Err(Error::new(err, None))
+        }
+        let set = RegexSet { raw: raw };
+        for pattern in patterns {
+            let regex = Regex::new_with_encoding(pattern.as_bytes(), config, ENCODING_UTF8)?;
+            let reg = regex.into_raw().unwrap();
+            let err = unsafe {
+                onig_regset_add(set.raw, reg)
+            };
+            if err != 0 {
+                unsafe {
+                    onig_free(reg);
+                }
+                return Err(Error::new(err, None))
+            }
+        }
+        Ok(set)
+    }
+
+    /// Returns the number of regexes in the set.
+    pub fn len(&self) -> usize {
+        unsafe {
+            onig_regset_number_of_regex(self.raw) as usize
+        }
+    }
+
+    /// Returns true if and only if the set contains no regexes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the leftmost match of any regex in the set, preferring the
+    /// regex added first at the same position. If no match is found, then
+    /// `None` is returned.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn find<'s>(&'s mut self, text: &str) -> Option<SetMatch<'s>> {
+        self.search_with_lead(text, RegSetLead::PositionLead, OPTION_NONE).unwrap()
+    }
+
+    /// Search all regexes of the set in string, choosing the match according
+    /// to `lead`.
+    ///
+    /// Returns which regex matched, the match position and the region of that
+    /// regex, or `None` if no regex matches.
+    pub fn search_with_lead<'s>(&'s mut self,
+                                text: &str,
+                                lead: RegSetLead,
+                                options: Options)
+                                -> Result<Option<SetMatch<'s>>, Error> {
+        self.search_in(text, 0, text.len(), lead, options)
+    }
+
+    /// Search all regexes of the set in string between byte offsets `start`
+    /// and `range`, choosing the match according to `lead`. See
+    /// `Regex::search_in` for the meaning of the window.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `start` or `range` is out of bounds or doesn't
+    /// lie on a character boundary of `text`.
+    pub fn search_in<'s>(&'s mut self,
+                         text: &str,
+                         start: usize,
+                         range: usize,
+                         lead: RegSetLead,
+                         options: Options)
+                         -> Result<Option<SetMatch<'s>>, Error> {
+        if !text.is_char_boundary(start) || !text.is_char_boundary(range) {
+            panic!("search window is not on a character boundary")
+        }
+        let text_bytes = text.as_bytes();
+        let (beg, end) = (
+            text_bytes.as_ptr(),
+            text_bytes[text_bytes.len()..].as_ptr()
+        );
+        let mut pos: c_int = 0;
+
+        let r = unsafe {
+            onig_regset_search(
+                self.raw,
+                beg,
+                end,
+                text_bytes[start..].as_ptr(),
+                text_bytes[range..].as_ptr(),
+                lead as c_int,
+                search_options(options),
+                &mut pos
+            )
+        };
+        resume_callout_panic();
+
+        Ok(search_result(r)?.map(|index| {
+            let region = unsafe {
+                &*onig_regset_get_region(self.raw, index as c_int)
+            };
+            SetMatch { index: index, pos: pos as usize, region: region }
+        }))
+    }
+}
+
+impl Drop for RegexSet {
+    fn drop(&mut self) {
+        // Frees the regexes and regions of the set as well.
+        unsafe {
+            onig_regset_free(self.raw);
+        }
+    }
+}
+
+/// Result of a `RegexSet` search.
+///
+/// `'s` is the lifetime of the set, which owns the region.
+#[derive(Debug)]
+pub struct SetMatch<'s> {
+    index: usize,
+    pos: usize,
+    region: &'s Region
+}
+
+impl<'s> SetMatch<'s> {
+    /// Returns the index of the matched regex in the set.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the byte position where the match starts.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the region holding capture groups of the matched regex.
+    pub fn region(&self) -> &'s Region {
+        self.region
+    }
+}
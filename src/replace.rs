@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+use super::{Error, Regex, Captures};
+
+/// Replacer describes types that can be used to replace matches in a string.
+///
+/// It is implemented for `&str`, which is treated as a template (see
+/// `Captures::expand`), and for closures `FnMut(&Captures) -> String`.
+pub trait Replacer {
+    /// Returns a possibly owned string that is used to replace the match
+    /// corresponding to the `caps` capture group.
+    fn reg_replace<'a>(&'a mut self, caps: &Captures) -> Cow<'a, str>;
+}
+
+impl<'t> Replacer for &'t str {
+    fn reg_replace<'a>(&'a mut self, caps: &Captures) -> Cow<'a, str> {
+        if !self.contains('$') && !self.contains('\\') {
+            return Cow::Borrowed(*self)
+        }
+        let mut dst = String::new();
+        caps.expand(self, &mut dst);
+        Cow::Owned(dst)
+    }
+}
+
+impl<F> Replacer for F where F: FnMut(&Captures) -> String {
+    fn reg_replace<'a>(&'a mut self, caps: &Captures) -> Cow<'a, str> {
+        Cow::Owned((*self)(caps))
+    }
+}
+
+impl Regex {
+    /// Replaces the leftmost-first match with the replacement provided. The
+    /// replacement can be a template string or a closure that returns the
+    /// replacement string for given captures.
+    ///
+    /// If no match is found, then a copy of the string is not made.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn replace<'t, R: Replacer>(&self, text: &'t str, rep: R) -> Cow<'t, str> {
+        self.replacen(text, 1, rep)
+    }
+
+    /// Replaces the leftmost-first match with the replacement provided, like
+    /// `replace`.
+    ///
+    /// Unlike `replace`, errors of Oniguruma engine are returned instead of
+    /// causing a panic.
+    pub fn try_replace<'t, R: Replacer>(&self,
+                                        text: &'t str,
+                                        rep: R)
+                                        -> Result<Cow<'t, str>, Error> {
+        self.try_replacen(text, 1, rep)
+    }
+
+    /// Replaces all non-overlapping matches in `text` with the replacement
+    /// provided. This is the same as calling `replacen` with `limit` set to
+    /// `0`.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn replace_all<'t, R: Replacer>(&self, text: &'t str, rep: R) -> Cow<'t, str> {
+        self.replacen(text, 0, rep)
+    }
+
+    /// Replaces all non-overlapping matches in `text` with the replacement
+    /// provided, like `replace_all`.
+    ///
+    /// Unlike `replace_all`, errors of Oniguruma engine are returned instead
+    /// of causing a panic.
+    pub fn try_replace_all<'t, R: Replacer>(&self,
+                                            text: &'t str,
+                                            rep: R)
+                                            -> Result<Cow<'t, str>, Error> {
+        self.try_replacen(text, 0, rep)
+    }
+
+    /// Replaces at most `limit` non-overlapping matches in `text` with the
+    /// replacement provided. If `limit` is `0`, then all non-overlapping
+    /// matches are replaced.
+    ///
+    /// # Panics
+    ///
+    /// This method may panic in the case of memory overflow during execution or
+    /// other internal errors of Oniguruma engine.
+    pub fn replacen<'t, R: Replacer>(&self, text: &'t str, limit: usize, rep: R) -> Cow<'t, str> {
+        self.try_replacen(text, limit, rep).unwrap()
+    }
+
+    /// Replaces at most `limit` non-overlapping matches in `text` with the
+    /// replacement provided, like `replacen`.
+    ///
+    /// Unlike `replacen`, errors of Oniguruma engine are returned instead of
+    /// causing a panic.
+    pub fn try_replacen<'t, R: Replacer>(&self,
+                                         text: &'t str,
+                                         limit: usize,
+                                         mut rep: R)
+                                         -> Result<Cow<'t, str>, Error> {
+        let mut new = String::new();
+        let mut last_match = 0;
+        let mut replaced = 0;
+        for caps in self.try_captures_iter(text) {
+            if limit > 0 && replaced >= limit {
+                break
+            }
+            let caps = caps?;
+            let (beg, end) = caps.pos(0).unwrap();
+            new.push_str(&text[last_match..beg]);
+            new.push_str(&rep.reg_replace(&caps));
+            last_match = end;
+            replaced += 1;
+        }
+        if replaced == 0 {
+            return Ok(Cow::Borrowed(text))
+        }
+        new.push_str(&text[last_match..]);
+        Ok(Cow::Owned(new))
+    }
+}
@@ -13,6 +13,7 @@ extern {
     static OnigSyntaxPerl: Syntax;
     static OnigSyntaxPerl_NG: Syntax;
     static OnigSyntaxRuby: Syntax;
+    static OnigSyntaxOniguruma: Syntax;
 
     fn onig_copy_syntax(to: *const Syntax, from: *const Syntax);
 
@@ -47,6 +48,8 @@ pub static SYNTAX_PERL: &'static Syntax = &OnigSyntaxPerl;
 pub static SYNTAX_PERL_NG: &'static Syntax = &OnigSyntaxPerl_NG;
 /// Ruby syntax (default)
 pub static SYNTAX_RUBY: &'static Syntax = &OnigSyntaxRuby;
+/// Oniguruma syntax, Ruby syntax extended with callouts
+pub static SYNTAX_ONIGURUMA: &'static Syntax = &OnigSyntaxOniguruma;
 
 
 type OnigCodePoint = c_ulong;
@@ -1,4 +1,10 @@
 use super::*;
+use std::borrow::Cow;
+use std::ops::ControlFlow;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str;
+use std::sync::{Mutex, MutexGuard};
 
 #[test]
 fn test_region_create() {
@@ -104,16 +110,39 @@ fn test_regex_subcapturespos() {
 }
 
 #[test]
+#[allow(deprecated)]
 fn test_regex_is_match() {
     let regex = Regex::new("he(l+)o").unwrap();
     assert!(regex.is_match("hello"));
     assert!(!regex.is_match("hello 2.0"));
+    assert_eq!(regex.try_is_match("hello").unwrap(), true);
+    assert_eq!(regex.try_is_match("hello 2.0").unwrap(), false);
+}
+
+#[test]
+fn test_regex_is_match_kinds() {
+    let regex = Regex::new("he(l+)o").unwrap();
+    assert!(regex.is_full_match("hello"));
+    assert!(!regex.is_full_match("hello 2.0"));
+    assert!(regex.is_prefix_match("hello 2.0"));
+    assert!(!regex.is_prefix_match("oh, hello"));
+    assert!(regex.is_search_match("oh, hello"));
+    assert!(!regex.is_search_match("oh, hi"));
+    assert_eq!(regex.try_is_search_match("oh, hello").unwrap(), true);
+    assert_eq!(regex.try_is_prefix_match("hello!").unwrap(), true);
+    assert_eq!(regex.try_is_full_match("hello!").unwrap(), false);
+    let regex = Regex::new("a|ab").unwrap();
+    assert!(regex.is_full_match("ab"));
+    assert!(regex.is_full_match("a"));
+    assert!(!regex.is_full_match("abc"));
+    assert!(!regex.is_full_match("b"));
+    assert!(Regex::new("x*").unwrap().is_full_match(""));
 }
 
 #[test]
 fn test_regex_find() {
     let regex = Regex::new("he(l+)o").unwrap();
-    assert_eq!(regex.find("hey, hello!"), Some((5, 10)));
+    assert_eq!(regex.find("hey, hello!").map(|m| m.range()), Some(5..10));
     assert_eq!(regex.find("hey, honey!"), None);
 }
 
@@ -152,3 +181,795 @@ fn test_regex_lens() {
     assert_eq!(regex.names_len(), 0);
     assert_eq!(regex.capture_histories_len(), 0);
 }
+
+#[test]
+fn test_regex_find_iter() {
+    let regex = Regex::new("\\d+").unwrap();
+    let matches = regex.find_iter("a1 b22 c333").map(|m| m.as_str()).collect::<Vec<_>>();
+    assert_eq!(matches, vec!["1", "22", "333"]);
+}
+
+#[test]
+fn test_regex_find_iter_empty() {
+    let regex = Regex::new("x*").unwrap();
+    let matches = regex.find_iter("aéxx").map(|m| m.range()).collect::<Vec<_>>();
+    assert_eq!(matches, vec![0..0, 1..1, 3..5, 5..5]);
+}
+
+#[test]
+fn test_regex_find_iter_lookbehind() {
+    let regex = Regex::new("(?<=a)b").unwrap();
+    let matches = regex.find_iter("abab").map(|m| m.range()).collect::<Vec<_>>();
+    assert_eq!(matches, vec![1..2, 3..4]);
+}
+
+#[test]
+fn test_regex_captures_iter() {
+    let regex = Regex::new("(\\w)=(\\d+)").unwrap();
+    let caps = regex.captures_iter("a=1, b=22").collect::<Vec<_>>();
+    assert_eq!(caps.len(), 2);
+    assert_eq!(caps[0].pos(0), Some((0, 3)));
+    assert_eq!(caps[0].at(2), Some("1"));
+    assert_eq!(caps[1].pos(1), Some((5, 6)));
+    assert_eq!(caps[1].at(2), Some("22"));
+}
+
+#[test]
+fn test_regex_captures_iter_context() {
+    let regex = Regex::new("\\G(a)").unwrap();
+    let caps = regex.captures_iter("aab").collect::<Vec<_>>();
+    assert_eq!(caps.len(), 2);
+    assert_eq!(caps[1].pos(1), Some((1, 2)));
+    let regex = Regex::new("(?<=-)(\\d)").unwrap();
+    let caps = regex.captures_iter("1-2-3").collect::<Vec<_>>();
+    assert_eq!(caps.iter().map(|c| c.at(1).unwrap()).collect::<Vec<_>>(), vec!["2", "3"]);
+}
+
+#[test]
+fn test_regex_replace() {
+    let regex = Regex::new("(\\w+)@(\\w+)").unwrap();
+    assert_eq!(regex.replace("a@b c@d", "$2:$1"), "b:a c@d");
+    assert_eq!(regex.replace_all("a@b c@d", "${2}/${1}"), "b/a d/c");
+    let regex = Regex::new("(?<user>\\w+)@(?<host>\\w+)").unwrap();
+    assert_eq!(regex.replace_all("a@b c@d", "${host}/${user}"), "b/a d/c");
+    assert_eq!(regex.replace_all("a@b c@d", "\\k<host>$$"), "b$ d$");
+    assert_eq!(regex.replacen("a@b c@d e@f", 2, "$9$x"), "$x $x e@f");
+}
+
+#[test]
+fn test_regex_replace_closure() {
+    let regex = Regex::new("\\d+").unwrap();
+    let result = regex.replace_all("1 22 333", |caps: &Captures| {
+        caps.at(0).unwrap().len().to_string()
+    });
+    assert_eq!(result, "1 2 3");
+}
+
+#[test]
+fn test_regex_replace_no_match() {
+    let regex = Regex::new("x").unwrap();
+    match regex.replace_all("abc", "y") {
+        Cow::Borrowed(s) => assert_eq!(s, "abc"),
+        Cow::Owned(..) => panic!("should not copy")
+    }
+}
+
+#[test]
+fn test_regex_split() {
+    let regex = Regex::new(",\\s*").unwrap();
+    let parts = regex.split("a, b,c,").collect::<Vec<_>>();
+    assert_eq!(parts, vec!["a", "b", "c", ""]);
+    let parts = regex.splitn("a, b,c,", 2).collect::<Vec<_>>();
+    assert_eq!(parts, vec!["a", "b,c,"]);
+    assert_eq!(regex.splitn("a,b", 0).count(), 0);
+    assert_eq!(regex.split("").collect::<Vec<_>>(), vec![""]);
+}
+
+#[test]
+fn test_regex_split_with_captures() {
+    let regex = Regex::new("(-)|(\\+)").unwrap();
+    let parts = regex.split("1-2+3").with_captures().collect::<Vec<_>>();
+    assert_eq!(parts, vec!["1", "-", "2", "+", "3"]);
+    let parts = regex.splitn("1-2+3", 2).with_captures().collect::<Vec<_>>();
+    assert_eq!(parts, vec!["1", "-", "2+3"]);
+}
+
+#[test]
+fn test_regex_search_in() {
+    let mut region = Region::new();
+    let regex = Regex::new("\\bfoo").unwrap();
+    let r = regex.search_in("xfoo foo", 1, 8, &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, Some(5));
+    assert_eq!(region.pos(0), Some((5, 8)));
+    let r = regex.search_in("xfoo foo", 1, 4, &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, None);
+    let regex = Regex::new("(?<=a)b").unwrap();
+    let r = regex.search_in("ab", 1, 2, &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, Some(1));
+}
+
+#[test]
+#[should_panic(expected = "search window is not on a character boundary")]
+fn test_regex_search_in_char_boundary() {
+    let mut region = Region::new();
+    let regex = Regex::new("a").unwrap();
+    let _ = regex.search_in("éa", 1, 3, &mut region, OPTION_NONE);
+}
+
+#[test]
+fn test_regex_rfind() {
+    let regex = Regex::new("ab").unwrap();
+    assert_eq!(regex.rfind("ab ab ab").map(|m| m.range()), Some(6..8));
+    assert_eq!(regex.rfind_at("ab ab ab", 5).map(|m| m.range()), Some(3..5));
+    assert_eq!(regex.rfind_at("ab ab ab", 2).map(|m| m.range()), Some(0..2));
+    assert_eq!(regex.rfind("ba"), None);
+}
+
+#[test]
+fn test_regex_rfind_iter() {
+    let regex = Regex::new("ab").unwrap();
+    let matches = regex.rfind_iter("ab éab ab").map(|m| m.range()).collect::<Vec<_>>();
+    assert_eq!(matches, vec![8..10, 5..7, 0..2]);
+    let regex = Regex::new("x*").unwrap();
+    let matches = regex.rfind_iter("éx").map(|m| m.range()).collect::<Vec<_>>();
+    assert_eq!(matches, vec![3..3, 2..3, 0..0]);
+}
+
+#[test]
+fn test_regex_match_at() {
+    let mut region = Region::new();
+    let regex = Regex::new("\\b\\w+").unwrap();
+    let r = regex.match_at("let x = 1", 4, &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, Some(1));
+    assert_eq!(region.pos(0), Some((4, 5)));
+    let r = regex.match_at("let x = 1", 1, &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, None);
+    let r = regex.match_at("let x = 1", 3, &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, None);
+}
+
+#[test]
+#[cfg(feature = "search-gpos")]
+fn test_regex_search_gpos() {
+    let mut region = Region::new();
+    let regex = Regex::new("\\G\\w").unwrap();
+    let r = regex.search_gpos("ab cd", 3, 0, 5, &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, Some(3));
+    assert_eq!(region.pos(0), Some((3, 4)));
+    let r = regex.search_gpos("ab cd", 2, 0, 5, &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, None);
+}
+
+#[test]
+fn test_bytes_regex_find() {
+    let regex = bytes::Regex::new(b"\x00\xff+").unwrap();
+    assert_eq!(regex.find(b"ab\x00\xff\xff\x00").map(|m| m.range()), Some(2..5));
+    let matches = regex.find_iter(b"\x00\xff \x00\xff\xff").map(|m| m.range())
+                       .collect::<Vec<_>>();
+    assert_eq!(matches, vec![0..2, 3..6]);
+}
+
+#[test]
+fn test_bytes_regex_captures() {
+    let regex = bytes::Regex::new(b"(\\w+)=([^;]*)").unwrap();
+    let caps = regex.captures(b"k=\xe9t\xe9;").unwrap();
+    assert_eq!(caps.at(1), Some(&b"k"[..]));
+    assert_eq!(caps.at(2), Some(&b"\xe9t\xe9"[..]));
+    let all = regex.captures_iter(b"a=1;b=").collect::<Vec<_>>();
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[1].at(2), Some(&b""[..]));
+}
+
+#[test]
+fn test_bytes_regex_latin1() {
+    let regex = bytes::Regex::new_with_encoding(b"\\w+", RegexConfig::default(),
+                                                ENCODING_ISO_8859_1).unwrap();
+    assert_eq!(regex.find(b" caf\xe9 ").map(|m| m.as_bytes()), Some(&b"caf\xe9"[..]));
+}
+
+#[test]
+fn test_bytes_regex_utf8_empty_matches() {
+    let regex = bytes::Regex::new_with_encoding(b"x*", RegexConfig::default(),
+                                                ENCODING_UTF8).unwrap();
+    let matches = regex.find_iter("éx".as_bytes()).map(|m| m.range()).collect::<Vec<_>>();
+    assert_eq!(matches, vec![0..0, 2..3, 3..3]);
+}
+
+#[test]
+fn test_regex_captures_name() {
+    let regex = Regex::new("(?<key>\\w+)=(?<value>\\w*)").unwrap();
+    let captures = regex.captures("a b=c").unwrap();
+    assert_eq!(captures.name("key"), Some("b"));
+    assert_eq!(captures.name_pos("value"), Some((4, 5)));
+    assert_eq!(captures.name("other"), None);
+    let named = captures.iter_named().collect::<Vec<_>>();
+    assert_eq!(named, vec![("key", Some("b")), ("value", Some("c"))]);
+}
+
+#[test]
+fn test_regex_captures_duplicate_name() {
+    let regex = Regex::new("(?<n>a)|(?<n>b)").unwrap();
+    assert_eq!(regex.name_to_group_numbers("n"), vec![1, 2]);
+    assert_eq!(regex.name_to_group_numbers("m"), vec![]);
+    let captures = regex.captures("b").unwrap();
+    assert_eq!(captures.name("n"), Some("b"));
+    let captures = regex.captures("a").unwrap();
+    assert_eq!(captures.name("n"), Some("a"));
+    assert_eq!(captures.iter_named().collect::<Vec<_>>(), vec![("n", Some("a"))]);
+}
+
+#[test]
+fn test_regex_capture_names() {
+    let regex = Regex::new("(?<b>x)(?<a>y)(?<b>z)").unwrap();
+    assert_eq!(regex.names_len(), 2);
+    let names = regex.capture_names().collect::<Vec<_>>();
+    assert_eq!(names, vec![("b", &[1, 3][..]), ("a", &[2][..])]);
+    let regex = Regex::new("(x)").unwrap();
+    assert_eq!(regex.capture_names().count(), 0);
+}
+
+#[test]
+fn test_regex_try_methods() {
+    let regex = Regex::new("(l+)").unwrap();
+    assert_eq!(regex.try_is_full_match("ll").unwrap(), true);
+    assert_eq!(regex.try_find("hello").unwrap().map(|m| m.range()), Some(2..4));
+    assert_eq!(regex.try_find("heo").unwrap(), None);
+    let captures = regex.try_captures("hello").unwrap().unwrap();
+    assert_eq!(captures.at(1), Some("ll"));
+    let matches = regex.try_find_iter("hello, lol").collect::<Result<Vec<_>, _>>().unwrap();
+    let matches = matches.iter().map(|m| m.range()).collect::<Vec<_>>();
+    assert_eq!(matches, vec![2..4, 7..8, 9..10]);
+    let captures = regex.try_captures_iter("lol").collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(captures.len(), 2);
+}
+
+#[test]
+fn test_regex_try_methods_errors() {
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let mut regex = Regex::new_with_config("l(?{fail})|,", config).unwrap();
+    regex.set_contents_callout(|args| {
+        if args.subject()[args.start()] == b'l' {
+            CalloutResult::Error(-100)
+        } else {
+            CalloutResult::Success
+        }
+    });
+    assert_eq!(regex.try_rfind("a,b").unwrap().map(|m| m.start()), Some(1));
+    assert_eq!(regex.try_rfind("hello").unwrap_err().code(), -100);
+    assert_eq!(regex.try_rfind_at("l, l", 2).unwrap().map(|m| m.start()), Some(1));
+    assert_eq!(regex.try_rfind_at("l, l", 0).unwrap_err().code(), -100);
+    let found = regex.try_rfind_iter("a,,b").collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(found.iter().map(|m| m.start()).collect::<Vec<_>>(), vec![2, 1]);
+    let mut found = regex.try_rfind_iter("l,");
+    assert_eq!(found.next().unwrap().unwrap().start(), 1);
+    assert_eq!(found.next().unwrap().unwrap_err().code(), -100);
+    assert!(found.next().is_none());
+
+    let pieces = regex.try_split("a,b").collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(pieces, vec!["a", "b"]);
+    let mut pieces = regex.try_splitn("a,l,b", 3);
+    assert_eq!(pieces.next().unwrap().unwrap(), "a");
+    assert_eq!(pieces.next().unwrap().unwrap_err().code(), -100);
+    assert!(pieces.next().is_none());
+
+    assert_eq!(regex.try_replace("a,b,c", "-").unwrap(), "a-b,c");
+    assert_eq!(regex.try_replace_all("a,b,c", "-").unwrap(), "a-b-c");
+    assert_eq!(regex.try_replacen("a,l", 0, "-").unwrap_err().code(), -100);
+    assert_eq!(regex.try_replace("lo", "-").unwrap_err().code(), -100);
+}
+
+#[test]
+fn test_bytes_try_methods() {
+    Callouts::register_named(ENCODING_ASCII, "FAIL_ON_L", CalloutIn::Progress, &[], |args| {
+        if args.subject()[args.start()] == b'l' {
+            CalloutResult::Error(-100)
+        } else {
+            CalloutResult::Success
+        }
+    }).unwrap();
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let regex = bytes::Regex::new_with_config(b"(\\w)(*FAIL_ON_L)", config).unwrap();
+    assert_eq!(regex.try_find(b"ab").unwrap().map(|m| m.range()), Some(0..1));
+    assert_eq!(regex.try_find(b"").unwrap(), None);
+    assert_eq!(regex.try_find(b"lo").unwrap_err().code(), -100);
+    assert_eq!(regex.try_captures(b"ab").unwrap().unwrap().at(1), Some(&b"a"[..]));
+    assert_eq!(regex.try_captures(b"l").unwrap_err().code(), -100);
+
+    let found = regex.try_find_iter(b"ab").collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(found.iter().map(|m| m.range()).collect::<Vec<_>>(), vec![0..1, 1..2]);
+    let mut found = regex.try_find_iter(b"al");
+    assert_eq!(found.next().unwrap().unwrap().as_bytes(), b"a");
+    assert_eq!(found.next().unwrap().unwrap_err().code(), -100);
+    assert!(found.next().is_none());
+    let mut captures = regex.try_captures_iter(b"al");
+    assert_eq!(captures.next().unwrap().unwrap().at(1), Some(&b"a"[..]));
+    assert_eq!(captures.next().unwrap().unwrap_err().code(), -100);
+    assert!(captures.next().is_none());
+}
+
+#[test]
+fn test_regex_match() {
+    let regex = Regex::new("b(c+)").unwrap();
+    let m = regex.find("abccd").unwrap();
+    assert_eq!(m.start(), 1);
+    assert_eq!(m.end(), 4);
+    assert_eq!(m.range(), 1..4);
+    assert_eq!(m.as_str(), "bcc");
+    assert_eq!(m.len(), 3);
+    assert!(!m.is_empty());
+    let captures = regex.captures("abccd").unwrap();
+    assert_eq!(captures.get(1).map(|m| m.as_str()), Some("cc"));
+    assert_eq!(captures.get(2), None);
+}
+
+#[test]
+fn test_region_with_capacity() {
+    let regex = Regex::new("(a)(b)?").unwrap();
+    let mut region = regex.new_region();
+    assert_eq!(region.len(), 3);
+    assert_eq!(region.pos(1), None);
+    regex.search_with_region("xa", &mut region, OPTION_NONE).unwrap();
+    assert_eq!(region.iter().collect::<Vec<_>>(), vec![Some((1, 2)), Some((1, 2)), None]);
+    region.resize(1);
+    assert_eq!(region.len(), 1);
+    assert_eq!(region.pos(0), None);
+}
+
+#[test]
+fn test_region_clone() {
+    let regex = Regex::new("(a)").unwrap();
+    let mut region = Region::new();
+    regex.search_with_region("ba", &mut region, OPTION_NONE).unwrap();
+    let copy = region.clone();
+    region.clear();
+    assert_eq!(region.pos(0), None);
+    assert_eq!(copy.len(), 2);
+    assert_eq!(copy.pos(1), Some((1, 2)));
+    let mut other = Region::new();
+    other.clone_from(&copy);
+    assert_eq!(other.pos(0), Some((1, 2)));
+}
+
+#[test]
+fn test_regex_posix_region() {
+    let regex = Regex::new("b(c)(x)?").unwrap();
+    let mut region = PosixRegion::with_capacity(1);
+    let r = regex.search_with_posix_region("abcd", &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, Some(1));
+    assert_eq!(region.len(), 3);
+    assert_eq!(region.pos(0), Some((1, 3)));
+    assert_eq!(region.pos(1), Some((2, 3)));
+    assert_eq!(region.pos(2), None);
+    let r = regex.match_with_posix_region("abcd", &mut region, OPTION_NONE).unwrap();
+    assert_eq!(r, None);
+    assert_eq!(region.pos(0), None);
+    let mut region = Region::new();
+    let r = regex.search_with_region("abc", &mut region, OPTION_POSIX_REGION).unwrap();
+    assert_eq!(r, Some(1));
+    assert_eq!(region.pos(1), Some((2, 3)));
+}
+
+#[test]
+fn test_regex_capture_tree_walk() {
+    let mut syntax = SYNTAX_RUBY.clone();
+    syntax.enable_operators(SYNTAX_OPERATOR_ATMARK_CAPTURE_HISTORY);
+    let regex = Regex::new_with_config("(?@(?@a(?@b))(?@c))", RegexConfig {
+        syntax: &syntax,
+        options: OPTION_NONE
+    }).unwrap();
+    let mut region = Region::new();
+    regex.search_with_region("-abc", &mut region, OPTION_NONE).unwrap();
+    let tree = region.tree().unwrap();
+    let groups = tree.depth_first().map(|n| n.group()).collect::<Vec<_>>();
+    assert_eq!(groups, vec![0, 1, 2, 3, 4]);
+    let groups = tree.breadth_first().map(|n| n.group()).collect::<Vec<_>>();
+    assert_eq!(groups, vec![0, 1, 2, 4, 3]);
+
+    let owned = tree.to_capture_tree();
+    region.clear();
+    assert_eq!(owned.len(), 1);
+    assert_eq!(owned[0].group(), 1);
+    assert_eq!(owned[0].pos(), (1, 4));
+    assert_eq!(owned[0].children().iter().map(|n| n.group()).collect::<Vec<_>>(), vec![2, 4]);
+    assert_eq!(owned[0][0][0].pos(), (2, 3));
+}
+
+#[test]
+fn test_regex_captures_tree() {
+    let mut syntax = SYNTAX_RUBY.clone();
+    syntax.enable_operators(SYNTAX_OPERATOR_ATMARK_CAPTURE_HISTORY);
+    let regex = Regex::new_with_config("(?@<word>\\w+)(?:,(?@\\w+))*", RegexConfig {
+        syntax: &syntax,
+        options: OPTION_NONE
+    }).unwrap();
+    let captures = regex.captures("ab,c,de").unwrap();
+    let tree = captures.tree().unwrap();
+    assert_eq!(tree.as_str(), "ab,c,de");
+    assert_eq!(tree.name(), None);
+    let children = tree.children().map(|n| (n.name(), n.as_str())).collect::<Vec<_>>();
+    assert_eq!(children, vec![(Some("word"), "ab")]);
+    assert_eq!(tree.get(0).unwrap().group(), 1);
+    assert!(tree.get(1).is_none());
+    assert_eq!(tree.depth_first().count(), 2);
+}
+
+#[test]
+fn test_regex_scan() {
+    let regex = Regex::new("(\\d)(\\d)?").unwrap();
+    let mut found = Vec::new();
+    let n = regex.scan("a1 23 4", OPTION_NONE, |i, pos, region| {
+        found.push((i, pos, region.pos(2)));
+        ControlFlow::Continue(())
+    }).unwrap();
+    assert_eq!(n, 3);
+    assert_eq!(found, vec![(0, 1, None), (1, 3, Some((4, 5))), (2, 6, None)]);
+}
+
+#[test]
+fn test_regex_scan_break() {
+    let regex = Regex::new("\\d").unwrap();
+    let mut found = Vec::new();
+    let n = regex.scan("1234", OPTION_NONE, |_, pos, _| {
+        found.push(pos);
+        if pos == 1 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+    }).unwrap();
+    assert_eq!(n, 2);
+    assert_eq!(found, vec![0, 1]);
+}
+
+#[test]
+#[should_panic(expected = "scan callback panic")]
+fn test_regex_scan_panic() {
+    let regex = Regex::new("\\d").unwrap();
+    let _ = regex.scan("1234", OPTION_NONE, |_, _, _| -> ControlFlow<()> {
+        panic!("scan callback panic")
+    });
+}
+
+#[test]
+fn test_regex_set() {
+    let mut set = RegexSet::new(&["b+", "a(b)", "c"]).unwrap();
+    assert_eq!(set.len(), 3);
+    {
+        let m = set.find("xxabbc").unwrap();
+        assert_eq!(m.index(), 1);
+        assert_eq!(m.pos(), 2);
+        assert_eq!(m.region().pos(1), Some((3, 4)));
+    }
+    let m = set.search_with_lead("xxabbc", RegSetLead::PriorityToRegexOrder, OPTION_NONE)
+        .unwrap()
+        .unwrap();
+    assert_eq!((m.index(), m.pos()), (0, 3));
+    assert_eq!(m.region().pos(0), Some((3, 5)));
+}
+
+#[test]
+fn test_regex_set_lead() {
+    let mut set = RegexSet::new(&["c", "b"]).unwrap();
+    let m = set.search_with_lead("abc", RegSetLead::RegexLead, OPTION_NONE).unwrap().unwrap();
+    assert_eq!((m.index(), m.pos()), (1, 1));
+    assert!(set.search_in("abc", 2, 2, RegSetLead::PositionLead, OPTION_NONE).unwrap().is_none());
+    assert!(set.find("xyz").is_none());
+}
+
+#[test]
+fn test_regex_set_invalid() {
+    assert_eq!(RegexSet::new(&["a", "\\p{foo}"]).unwrap_err().code(), -223);
+    let config = RegexConfig { options: OPTION_FIND_LONGEST, syntax: SYNTAX_RUBY };
+    assert!(RegexSet::new_with_config(&["a"], config).is_err());
+}
+
+#[test]
+fn test_regex_match_with_param() {
+    let regex = Regex::new("(?:a|a)*b").unwrap();
+    let text = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaac";
+    let mut region = Region::new();
+    let mut param = MatchParam::new();
+    param.set_retry_limit_in_match(1000);
+    let err = regex.match_with_param(text, 0, &mut region, OPTION_NONE, &param).unwrap_err();
+    assert!(err.is_limit_exceeded());
+
+    param.set_retry_limit_in_match(100000);
+    let r = regex.match_with_param("aab", 0, &mut region, OPTION_NONE, &param);
+    assert_eq!(r.unwrap(), Some(3));
+}
+
+#[test]
+fn test_regex_search_with_param() {
+    let regex = Regex::new("b+").unwrap();
+    let mut region = Region::new();
+    let param = MatchParam::new();
+    let r = regex.search_with_param("aabbc", 0, 5, &mut region, OPTION_NONE, &param);
+    assert_eq!(r.unwrap(), Some(2));
+    assert_eq!(region.pos(0), Some((2, 4)));
+    assert!(!Regex::new("(").unwrap_err().is_limit_exceeded());
+}
+
+#[test]
+fn test_regex_subexp_call_limit_exceeded() {
+    let regex = Regex::new("\\A(?<p>a\\g<p>?b)\\z").unwrap();
+    let text = "aaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbb";
+    let guard = EngineLimitsGuard::new();
+    EngineLimits::set_subexp_call_limit_in_search(5).unwrap();
+    let r = regex.try_find(text);
+    drop(guard);
+    let err = r.unwrap_err();
+    assert_eq!(err.code(), -19);
+    assert!(err.is_limit_exceeded());
+    assert!(regex.try_find(text).unwrap().is_some());
+}
+
+// Serializes the tests changing process-wide engine limits.
+static ENGINE_LIMITS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Holds `ENGINE_LIMITS_LOCK` and restores the engine limits read when it was
+/// taken once dropped, even if the test panics.
+struct EngineLimitsGuard {
+    match_stack_limit_size: u32,
+    retry_limit_in_match: u64,
+    retry_limit_in_search: u64,
+    subexp_call_limit_in_search: u64,
+    parse_depth_limit: u32,
+    subexp_call_max_nest_level: u32,
+    _lock: MutexGuard<'static, ()>
+}
+
+impl EngineLimitsGuard {
+    fn new() -> EngineLimitsGuard {
+        let lock = ENGINE_LIMITS_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        EngineLimitsGuard {
+            match_stack_limit_size: EngineLimits::match_stack_limit_size(),
+            retry_limit_in_match: EngineLimits::retry_limit_in_match(),
+            retry_limit_in_search: EngineLimits::retry_limit_in_search(),
+            subexp_call_limit_in_search: EngineLimits::subexp_call_limit_in_search(),
+            parse_depth_limit: EngineLimits::parse_depth_limit(),
+            subexp_call_max_nest_level: EngineLimits::subexp_call_max_nest_level(),
+            _lock: lock
+        }
+    }
+}
+
+impl Drop for EngineLimitsGuard {
+    fn drop(&mut self) {
+        EngineLimits::set_match_stack_limit_size(self.match_stack_limit_size).unwrap();
+        EngineLimits::set_retry_limit_in_match(self.retry_limit_in_match).unwrap();
+        EngineLimits::set_retry_limit_in_search(self.retry_limit_in_search).unwrap();
+        EngineLimits::set_subexp_call_limit_in_search(self.subexp_call_limit_in_search).unwrap();
+        EngineLimits::set_parse_depth_limit(self.parse_depth_limit).unwrap();
+        EngineLimits::set_subexp_call_max_nest_level(self.subexp_call_max_nest_level).unwrap();
+    }
+}
+
+#[test]
+fn test_engine_limits() {
+    let _guard = EngineLimitsGuard::new();
+
+    let depth = EngineLimits::parse_depth_limit();
+    EngineLimits::set_parse_depth_limit(depth + 1).unwrap();
+    assert_eq!(EngineLimits::parse_depth_limit(), depth + 1);
+
+    let level = EngineLimits::subexp_call_max_nest_level();
+    EngineLimits::set_subexp_call_max_nest_level(level + 1).unwrap();
+    assert_eq!(EngineLimits::subexp_call_max_nest_level(), level + 1);
+    assert!(EngineLimits::set_subexp_call_max_nest_level(u32::MAX).is_err());
+    assert_eq!(EngineLimits::subexp_call_max_nest_level(), level + 1);
+    assert!(EngineLimits::set_capture_num_limit(u32::MAX).is_err());
+
+    let retry = EngineLimits::retry_limit_in_match();
+    EngineLimits::set_retry_limit_in_match(retry + 1).unwrap();
+    assert_eq!(EngineLimits::retry_limit_in_match(), retry + 1);
+}
+
+#[test]
+fn test_named_callout() {
+    Callouts::register_named(ENCODING_UTF8, "OCTET", CalloutIn::Progress, &[CalloutArgType::Long],
+                             |args| {
+        let group = match args.arg(0) {
+            Some(CalloutArg::Long(n)) => n as usize,
+            _ => return CalloutResult::Error(-100)
+        };
+        let (beg, end) = args.capture_pos(group).unwrap();
+        let octet = str::from_utf8(&args.subject()[beg..end]).unwrap();
+        if octet.parse::<u32>().unwrap() <= 255 {
+            CalloutResult::Success
+        } else {
+            CalloutResult::Fail
+        }
+    }).unwrap();
+
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let regex = Regex::new_with_config(r"\b(\d{1,3})(*OCTET{1})\b", config).unwrap();
+    let octets: Vec<&str> = regex.find_iter("10 300 255 999").map(|m| m.as_str()).collect();
+    assert_eq!(octets, vec!["10", "255"]);
+
+    let err = Regex::new_with_config(r"(*OCTET{x})", config).unwrap_err();
+    assert!(err.code() < 0);
+}
+
+#[test]
+fn test_named_callout_error() {
+    let id = Callouts::register_named(ENCODING_UTF8, "ABORT", CalloutIn::Progress, &[],
+                                      |_| CalloutResult::Error(-100)).unwrap();
+    assert!(id >= 0);
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let regex = Regex::new_with_config("a(*ABORT)", config).unwrap();
+    assert_eq!(regex.try_find("xa").unwrap_err().code(), -100);
+    assert!(regex.try_find("xb").unwrap().is_none());
+}
+
+#[test]
+fn test_named_callout_invalid_error() {
+    let id = Callouts::register_named(ENCODING_UTF8, "CHECKED", CalloutIn::Progress, &[],
+                                      |_| CalloutResult::Success).unwrap();
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let mut regex = Regex::new_with_config("a(*CHECKED)", config).unwrap();
+    for &code in &[1, 0, -1, -2, -3, i32::MIN] {
+        regex.set_named_callout(id, move |_| CalloutResult::Error(code));
+        assert_eq!(regex.try_find("a").unwrap_err().code(), -30);
+    }
+    regex.set_named_callout(id, |_| CalloutResult::Error(-4));
+    assert_eq!(regex.try_find("a").unwrap_err().code(), -4);
+}
+
+#[test]
+fn test_named_callout_per_regex() {
+    let id = Callouts::register_named(ENCODING_UTF8, "PICK", CalloutIn::Progress, &[],
+                                      |_| CalloutResult::Success).unwrap();
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let mut only_b = Regex::new_with_config(r"\w(*PICK)", config).unwrap();
+    let mut only_c = Regex::new_with_config(r"\w(*PICK)", config).unwrap();
+    let plain = Regex::new_with_config(r"\w(*PICK)", config).unwrap();
+    only_b.set_named_callout(id, |args| {
+        if args.subject()[args.start()] == b'b' { CalloutResult::Success } else { CalloutResult::Fail }
+    });
+    only_c.set_named_callout(id, |args| {
+        if args.subject()[args.start()] == b'c' { CalloutResult::Success } else { CalloutResult::Fail }
+    });
+    assert_eq!(only_b.find("abc").unwrap().as_str(), "b");
+    assert_eq!(only_c.find("abc").unwrap().as_str(), "c");
+    assert_eq!(plain.find("abc").unwrap().as_str(), "a");
+    assert_eq!(only_b.clone().find("abc").unwrap().as_str(), "b");
+}
+
+#[test]
+#[should_panic(expected = "callout panicked")]
+fn test_named_callout_panic() {
+    Callouts::register_named(ENCODING_UTF8, "PANIC", CalloutIn::Progress, &[],
+                             |_| panic!("callout panicked")).unwrap();
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    Regex::new_with_config("a(*PANIC)", config).unwrap().find("a");
+}
+
+#[test]
+fn test_contents_callout() {
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let mut regex = Regex::new_with_config(r"\b(\w+)(?{len<3})\b", config).unwrap();
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let log = seen.clone();
+    regex.set_contents_callout(move |args| {
+        assert_eq!(args.contents(), Some(&b"len<3"[..]));
+        let (beg, end) = args.capture_pos(1).unwrap();
+        assert_eq!(args.current(), end);
+        log.borrow_mut().push((beg, end));
+        if end - beg < 3 { CalloutResult::Success } else { CalloutResult::Fail }
+    });
+    let words: Vec<&str> = regex.find_iter("abcd ef").map(|m| m.as_str()).collect();
+    assert_eq!(words, vec!["ef"]);
+    assert_eq!(seen.borrow()[0], (0, 4));
+
+    let param = MatchParam::new();
+    let mut region = Region::new();
+    let r = regex.search_with_param("abcd ef", 0, 7, &mut region, OPTION_NONE, &param);
+    assert_eq!(r.unwrap(), Some(5));
+}
+
+#[test]
+fn test_contents_callout_retraction() {
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let mut regex = Regex::new_with_config("a(?{step}X)(?:b|c)", config).unwrap();
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let log = calls.clone();
+    regex.set_contents_callout(move |args| {
+        log.borrow_mut().push(args.callout_in());
+        CalloutResult::Success
+    });
+    assert!(regex.find("ad").is_none());
+    assert_eq!(*calls.borrow(), vec![CalloutIn::Progress, CalloutIn::Retraction]);
+}
+
+#[test]
+fn test_builtin_callout_data() {
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let regex = Regex::new_with_config("(?:a(*COUNT[A])|b(*TOTAL_COUNT[B]))+c", config).unwrap();
+    assert_eq!(regex.callout_num_by_tag("A"), Some(1));
+    assert_eq!(regex.callout_num_by_tag("B"), Some(2));
+    assert_eq!(regex.callout_num_by_tag("Z"), None);
+
+    let param = MatchParam::new();
+    let mut region = Region::new();
+    let r = regex.search_with_param("xabaac", 0, 6, &mut region, OPTION_NONE, &param);
+    assert_eq!(r.unwrap(), Some(1));
+    assert_eq!(param.callout_data_by_tag(&regex, "A", 0).unwrap(), Some(CalloutArg::Long(3)));
+    assert_eq!(param.callout_data(&regex, 2, 0).unwrap(), Some(CalloutArg::Long(1)));
+    assert!(param.callout_data_by_tag(&regex, "Z", 0).is_err());
+}
+
+#[test]
+fn test_builtin_callout_data_checks() {
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_ONIGURUMA };
+    let regex = Regex::new_with_config("b(*TOTAL_COUNT[T])x|c", config).unwrap();
+    let other = Regex::new_with_config("b(*TOTAL_COUNT[T])x|c", config).unwrap();
+    let param = MatchParam::new();
+    assert!(param.callout_data(&regex, 1, 0).is_err());
+    assert!(param.callout_data_by_tag(&regex, "T", 0).is_err());
+
+    let mut region = Region::new();
+    let r = regex.search_with_param("bc", 0, 2, &mut region, OPTION_NONE, &param);
+    assert_eq!(r.unwrap(), Some(1));
+    // set while matching at 0, kept by the match at 1
+    assert_eq!(param.callout_data(&regex, 1, 0).unwrap(), Some(CalloutArg::Long(1)));
+    assert_eq!(param.callout_data_by_tag(&regex, "T", 0).unwrap(), Some(CalloutArg::Long(1)));
+    assert_eq!(param.callout_data(&regex.clone(), 1, 0).unwrap(), Some(CalloutArg::Long(1)));
+    assert_eq!(param.callout_data(&regex, 1, 4).unwrap(), None);
+
+    assert!(param.callout_data(&regex, 0, 0).is_err());
+    assert!(param.callout_data(&regex, 2, 0).is_err());
+    assert!(param.callout_data(&regex, -1, 0).is_err());
+    assert!(param.callout_data(&regex, 1, 5).is_err());
+    assert!(param.callout_data_by_tag(&regex, "T", 5).is_err());
+    assert!(param.callout_data(&other, 1, 0).is_err());
+    assert!(param.callout_data_by_tag(&other, "T", 0).is_err());
+
+    let r = other.match_with_param("bx", 0, &mut region, OPTION_NONE, &param);
+    assert_eq!(r.unwrap(), Some(2));
+    assert_eq!(param.callout_data(&other, 1, 0).unwrap(), Some(CalloutArg::Long(1)));
+    assert!(param.callout_data(&regex, 1, 0).is_err());
+}
+
+#[test]
+fn test_regex_each_match() {
+    let regex = Regex::new("(a|ab)(c|bcd)?").unwrap();
+    let mut paths = Vec::new();
+    let n = regex.each_match("abcd", OPTION_NONE, |pos, region| {
+        paths.push((pos, region.pos(0).unwrap(), region.pos(1).unwrap()));
+        ControlFlow::Continue(())
+    }).unwrap();
+    assert_eq!(n, 4);
+    assert_eq!(paths, vec![
+        (0, (0, 4), (0, 1)),
+        (0, (0, 1), (0, 1)),
+        (0, (0, 3), (0, 2)),
+        (0, (0, 2), (0, 2)),
+    ]);
+
+    let n = regex.each_match("abcd", OPTION_NONE, |_, _| ControlFlow::Break(())).unwrap();
+    assert_eq!(n, 1);
+    assert_eq!(regex.each_match("xyz", OPTION_NONE, |_, _| ControlFlow::Continue(())).unwrap(), 0);
+    assert_eq!(regex.find("abcd").unwrap().as_str(), "abcd");
+
+    let mut region = Region::new();
+    let r = regex.search_with_region("xa", &mut region, OPTION_CALLBACK_EACH_MATCH);
+    assert_eq!(r.unwrap(), Some(1));
+    let r = regex.match_with_region("a", &mut region, OPTION_CALLBACK_EACH_MATCH);
+    assert_eq!(r.unwrap(), Some(1));
+}
+
+#[test]
+fn test_regex_new_with_warnings() {
+    let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_RUBY };
+    let (regex, warnings) = Regex::new_with_warnings("a]b(?:c+)*", config).unwrap();
+    assert!(regex.is_search_match("a]bcc"));
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].message().contains("']' without escape"));
+    assert!(warnings[0].message().contains("a]b(?:c+)*"));
+    assert_eq!(warnings[0].kind(), WarningKind::CcOpNotEscaped);
+    assert!(!warnings[0].is_verbose());
+    assert_eq!(warnings[1].kind(), WarningKind::RedundantNestedRepeat);
+    assert!(warnings[1].is_verbose());
+
+    // not collected, passed on to the default handler
+    Regex::new_with_config("a]b", config).unwrap();
+    let (_, warnings) = Regex::new_with_warnings("abc", config).unwrap();
+    assert!(warnings.is_empty());
+    assert!(Regex::new_with_warnings("(", config).is_err());
+}
@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+use std::{iter, ops};
+use super::{CaptureTreeNode, Captures};
+
+impl CaptureTreeNode {
+    /// Creates an iterator over this node and all its descendants in
+    /// depth-first (pre-order) order.
+    pub fn depth_first<'r>(&'r self) -> CaptureTreeWalk<'r> {
+        CaptureTreeWalk::new(vec![self], WalkOrder::DepthFirst)
+    }
+
+    /// Creates an iterator over this node and all its descendants in
+    /// breadth-first order.
+    pub fn breadth_first<'r>(&'r self) -> CaptureTreeWalk<'r> {
+        CaptureTreeWalk::new(vec![self], WalkOrder::BreadthFirst)
+    }
+
+    /// Copies the subtree rooted at this node into an owned `CaptureTree`.
+    pub fn to_capture_tree(&self) -> CaptureTree {
+        CaptureTree {
+            group: self.group(),
+            pos: self.pos(),
+            children: self.childs().map(|node| node.to_capture_tree()).collect()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WalkOrder {
+    DepthFirst,
+    BreadthFirst,
+    /// Only the initial nodes, without descending into them.
+    Flat
+}
+
+/// An iterator walking a capture history tree either depth-first or
+/// breadth-first.
+///
+/// `'r` is the lifetime of the region owning the tree.
+#[derive(Debug)]
+pub struct CaptureTreeWalk<'r> {
+    pending: VecDeque<&'r CaptureTreeNode>,
+    order: WalkOrder
+}
+
+impl<'r> CaptureTreeWalk<'r> {
+    fn new(nodes: Vec<&'r CaptureTreeNode>, order: WalkOrder) -> CaptureTreeWalk<'r> {
+        CaptureTreeWalk { pending: nodes.into_iter().collect(), order: order }
+    }
+}
+
+impl<'r> iter::Iterator for CaptureTreeWalk<'r> {
+    type Item = &'r CaptureTreeNode;
+
+    fn next(&mut self) -> Option<&'r CaptureTreeNode> {
+        let node = match self.pending.pop_front() {
+            Some(node) => node,
+            None => return None
+        };
+        match self.order {
+            WalkOrder::DepthFirst => {
+                for child in node.childs().collect::<Vec<_>>().into_iter().rev() {
+                    self.pending.push_front(child);
+                }
+            }
+            WalkOrder::BreadthFirst => self.pending.extend(node.childs()),
+            WalkOrder::Flat => {}
+        }
+        Some(node)
+    }
+}
+
+/// An owned copy of a capture history tree, independent of the region it
+/// was taken from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureTree {
+    group: usize,
+    pos: (usize, usize),
+    children: Vec<CaptureTree>
+}
+
+impl CaptureTree {
+    /// Returns the number of the capture group this node corresponds to.
+    pub fn group(&self) -> usize {
+        self.group
+    }
+
+    /// Returns the start and end byte positions of the captured text.
+    pub fn pos(&self) -> (usize, usize) {
+        self.pos
+    }
+
+    /// Returns the number of child nodes.
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    /// Returns true if and only if the node has no children.
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Returns the child nodes in order of capture.
+    pub fn children(&self) -> &[CaptureTree] {
+        &self.children
+    }
+}
+
+impl ops::Index<usize> for CaptureTree {
+    type Output = CaptureTree;
+
+    fn index(&self, index: usize) -> &CaptureTree {
+        &self.children[index]
+    }
+}
+
+impl<'t> Captures<'t> {
+    /// Returns the root of the capture history tree of this match, or `None`
+    /// if the regex has no capture history groups `(?@...)`.
+    pub fn tree<'c>(&'c self) -> Option<CapturesNode<'c, 't>> {
+        self.region().tree().map(|node| CapturesNode { node: node, caps: self })
+    }
+}
+
+/// Node of the capture history tree of a match, resolving captured text and
+/// group names.
+///
+/// `'c` is the lifetime of the captures and `'t` is the lifetime of the
+/// matched text.
+#[derive(Clone, Copy, Debug)]
+pub struct CapturesNode<'c, 't: 'c> {
+    node: &'c CaptureTreeNode,
+    caps: &'c Captures<'t>
+}
+
+impl<'c, 't> CapturesNode<'c, 't> {
+    /// Returns the underlying capture history tree node.
+    pub fn node(&self) -> &'c CaptureTreeNode {
+        self.node
+    }
+
+    /// Returns the number of the capture group this node corresponds to.
+    pub fn group(&self) -> usize {
+        self.node.group()
+    }
+
+    /// Returns the name of the capture group this node corresponds to, or
+    /// `None` if the group is unnamed.
+    pub fn name(&self) -> Option<&'c str> {
+        let group = self.group();
+        self.caps
+            .regex()
+            .names()
+            .iter()
+            .find(|&&(_, ref groups)| groups.contains(&group))
+            .map(|&(ref name, _)| &name[..])
+    }
+
+    /// Returns the start and end byte positions of the captured text.
+    pub fn pos(&self) -> (usize, usize) {
+        self.node.pos()
+    }
+
+    /// Returns the captured text.
+    pub fn as_str(&self) -> &'t str {
+        let (beg, end) = self.pos();
+        &self.caps.text()[beg..end]
+    }
+
+    /// Returns the number of child nodes.
+    pub fn len(&self) -> usize {
+        self.node.len()
+    }
+
+    /// Returns true if and only if the node has no children.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `i`th child node, or `None` if there is no such child.
+    pub fn get(&self, i: usize) -> Option<CapturesNode<'c, 't>> {
+        if i < self.len() {
+            Some(CapturesNode { node: &self.node[i], caps: self.caps })
+        } else {
+            None
+        }
+    }
+
+    /// Creates an iterator over the child nodes.
+    pub fn children(&self) -> CapturesNodes<'c, 't> {
+        CapturesNodes {
+            walk: CaptureTreeWalk::new(self.node.childs().collect(), WalkOrder::Flat),
+            caps: self.caps
+        }
+    }
+
+    /// Creates an iterator over this node and all its descendants in
+    /// depth-first (pre-order) order.
+    pub fn depth_first(&self) -> CapturesNodes<'c, 't> {
+        CapturesNodes { walk: self.node.depth_first(), caps: self.caps }
+    }
+
+    /// Creates an iterator over this node and all its descendants in
+    /// breadth-first order.
+    pub fn breadth_first(&self) -> CapturesNodes<'c, 't> {
+        CapturesNodes { walk: self.node.breadth_first(), caps: self.caps }
+    }
+}
+
+/// An iterator over nodes of the capture history tree of a match.
+///
+/// `'c` is the lifetime of the captures and `'t` is the lifetime of the
+/// matched text.
+#[derive(Debug)]
+pub struct CapturesNodes<'c, 't: 'c> {
+    walk: CaptureTreeWalk<'c>,
+    caps: &'c Captures<'t>
+}
+
+impl<'c, 't> iter::Iterator for CapturesNodes<'c, 't> {
+    type Item = CapturesNode<'c, 't>;
+
+    fn next(&mut self) -> Option<CapturesNode<'c, 't>> {
+        self.walk.next().map(|node| CapturesNode { node: node, caps: self.caps })
+    }
+}
@@ -0,0 +1,140 @@
+use libc::{c_char, c_int};
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::sync::Once;
+
+use super::{Encoding, Error, Regex, RegexConfig, ENCODING_UTF8};
+
+#[link(name="onig")]
+extern {
+    fn onig_initialize(encodings: *const *const Encoding, n: c_int) -> c_int;
+    fn onig_set_warn_func(f: extern "C" fn(*const c_char));
+    fn onig_set_verb_warn_func(f: extern "C" fn(*const c_char));
+    fn onig_null_warn(s: *const c_char);
+}
+
+/// The kind of a `Warning`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningKind {
+    /// A character class or pattern metacharacter such as `]` or `-` is
+    /// used without escape.
+    CcOpNotEscaped,
+    /// A nested repeat such as `(?:a+)*` is redundant or was replaced, as
+    /// reported with `SYNTAX_BEHAVIOR_WARN_REDUNDANT_NESTED_REPEAT`.
+    RedundantNestedRepeat,
+    /// Any other warning.
+    Other
+}
+
+impl WarningKind {
+    fn from_message(message: &str) -> WarningKind {
+        if message.contains("without escape") {
+            WarningKind::CcOpNotEscaped
+        } else if message.contains("nested repeat operator") {
+            WarningKind::RedundantNestedRepeat
+        } else {
+            WarningKind::Other
+        }
+    }
+}
+
+/// A warning reported by Oniguruma while compiling a pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    kind: WarningKind,
+    message: String,
+    verbose: bool
+}
+
+impl Warning {
+    /// What the warning is about.
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+
+    /// The message, which quotes the offending pattern.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns true for warnings Oniguruma only reports in verbose mode, such
+    /// as the one enabled by `SYNTAX_BEHAVIOR_WARN_REDUNDANT_NESTED_REPEAT`.
+    pub fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+static INSTALL_WARN_FUNCS: Once = Once::new();
+
+thread_local! {
+    // Warnings of the pattern being compiled on this thread, if collected.
+    static WARNINGS: RefCell<Option<Vec<Warning>>> = RefCell::new(None);
+}
+
+fn push_warning(s: *const c_char, verbose: bool) {
+    let collected = WARNINGS.try_with(|warnings| {
+        match *warnings.borrow_mut() {
+            Some(ref mut warnings) => {
+                let message = unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned();
+                warnings.push(Warning {
+                    kind: WarningKind::from_message(&message),
+                    message: message,
+                    verbose: verbose
+                });
+                true
+            }
+            None => false
+        }
+    });
+    if collected != Ok(true) {
+        // Oniguruma has no getter for the handlers, the ones replaced are
+        // its defaults
+        unsafe { onig_null_warn(s) }
+    }
+}
+
+extern "C" fn warn_func(s: *const c_char) {
+    push_warning(s, false)
+}
+
+extern "C" fn verb_warn_func(s: *const c_char) {
+    push_warning(s, true)
+}
+
+impl Regex {
+    /// Create a new Regex like `new_with_config`, also returning the
+    /// warnings reported while compiling the pattern.
+    ///
+    /// Once this method has been called, Oniguruma's warning handlers are
+    /// replaced for the whole process. Warnings of patterns compiled by
+    /// other constructors are still passed to Oniguruma's default handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oniguruma::{Regex, RegexConfig, WarningKind, OPTION_NONE, SYNTAX_RUBY};
+    ///
+    /// let config = RegexConfig { options: OPTION_NONE, syntax: SYNTAX_RUBY };
+    /// let (_, warnings) = Regex::new_with_warnings("(?:a*)+", config).unwrap();
+    /// assert_eq!(warnings.len(), 1);
+    /// assert_eq!(warnings[0].kind(), WarningKind::RedundantNestedRepeat);
+    /// assert!(warnings[0].is_verbose());
+    /// ```
+    pub fn new_with_warnings(pattern: &str,
+                             config: RegexConfig)
+                             -> Result<(Regex, Vec<Warning>), Error> {
+        INSTALL_WARN_FUNCS.call_once(|| unsafe {
+            // initialize explicitly, otherwise Oniguruma warns about it on the
+            // first compilation
+            let encodings = [ENCODING_UTF8 as *const Encoding];
+            onig_initialize(encodings.as_ptr(), encodings.len() as c_int);
+            onig_set_warn_func(warn_func);
+            onig_set_verb_warn_func(verb_warn_func);
+        });
+        WARNINGS.with(|warnings| *warnings.borrow_mut() = Some(Vec::new()));
+        let regex = Regex::new_with_encoding(pattern.as_bytes(), config, ENCODING_UTF8);
+        let warnings = WARNINGS.with(|warnings| warnings.borrow_mut().take())
+            .unwrap_or_default();
+        regex.map(|regex| (regex, warnings))
+    }
+}